@@ -0,0 +1,349 @@
+use std::collections::HashSet;
+use unidecode::unidecode;
+
+use crate::tokens;
+
+/// A stage of an [`Analyzer`], transforming or dropping each token.
+pub trait TokenFilter: Send + Sync {
+    /// Returns the transformed `token`, or `None` to drop it.
+    fn apply(&self, token: String) -> Option<String>;
+
+    /// Returns the tokens replacing `token`, which is the result of [`TokenFilter::apply`] by default.
+    ///
+    /// Stages indexing several tokens for a word, such as [`NumberBuckets`], override this method. Each token goes through the next stages.
+    fn apply_all(&self, token: String) -> Vec<String> {
+        self.apply(token).into_iter().collect()
+    }
+}
+
+/// A tokenizer pipeline made of ordered [`TokenFilter`] stages.
+///
+/// The text is split on whitespace, then each word goes through every stage in order.
+/// Words dropped by a stage or left empty are not indexed.
+///
+/// # Example
+///
+/// ```
+/// # use index_bloom::analyzer::{Analyzer, Length, Lowercase, StopWords};
+/// let analyzer = Analyzer::new()
+///     .with_filter(Lowercase)
+///     .with_filter(StopWords::new(vec!["the", "is"]))
+///     .with_filter(Length::new(4, 10));
+/// assert_eq!(vec!["quick", "brown"], analyzer.analyze("The quick brown fox is"));
+/// ```
+pub struct Analyzer {
+    filters: Vec<Box<dyn TokenFilter>>
+}
+
+impl Analyzer {
+    /// Constructs a new `Analyzer` without any stage.
+    pub fn new() -> Self {
+        Analyzer {
+            filters: Vec::new()
+        }
+    }
+
+    /// Constructs a new `Analyzer` from the given ordered stages.
+    pub fn from_filters(filters: Vec<Box<dyn TokenFilter>>) -> Self {
+        Analyzer {
+            filters
+        }
+    }
+
+    /// Append a stage at the end of the pipeline.
+    pub fn with_filter<F: TokenFilter + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Split `text` into tokens going through every stage of the pipeline.
+    pub fn analyze(&self, text: &str) -> Vec<String> {
+        text.split_whitespace()
+            .flat_map(|word| {
+                self.filters.iter().fold(vec![word.to_string()], |tokens, filter| {
+                    tokens.into_iter().flat_map(|token| filter.apply_all(token)).collect()
+                })
+            })
+            .filter(|token| !token.is_empty())
+            .collect()
+    }
+}
+
+/// The default pipeline, equivalent to the built-in tokenizer: [`Unidecode`], [`StripPunctuation`] and [`Lowercase`].
+impl Default for Analyzer {
+    fn default() -> Self {
+        Analyzer::new()
+            .with_filter(Unidecode)
+            .with_filter(StripPunctuation)
+            .with_filter(Lowercase)
+    }
+}
+
+/// Converts tokens to lowercase.
+pub struct Lowercase;
+
+impl TokenFilter for Lowercase {
+    fn apply(&self, token: String) -> Option<String> {
+        Some(token.to_lowercase())
+    }
+}
+
+/// Transliterates tokens to ASCII, removing accents.
+pub struct Unidecode;
+
+impl TokenFilter for Unidecode {
+    fn apply(&self, token: String) -> Option<String> {
+        Some(unidecode(&token))
+    }
+}
+
+/// Removes punctuation characters from tokens.
+pub struct StripPunctuation;
+
+impl TokenFilter for StripPunctuation {
+    fn apply(&self, token: String) -> Option<String> {
+        Some(tokens::clean_word(&token))
+    }
+}
+
+/// Drops the given words.
+///
+/// Stop words are compared as is, so this stage usually comes after normalization stages such as [`Lowercase`].
+pub struct StopWords {
+    words: HashSet<String>
+}
+
+impl StopWords {
+    /// Constructs a new stage dropping the given `words`.
+    pub fn new<I, S>(words: I) -> Self
+        where I: IntoIterator<Item = S>, S: Into<String> {
+        StopWords {
+            words: words.into_iter().map(Into::into).collect()
+        }
+    }
+}
+
+impl TokenFilter for StopWords {
+    fn apply(&self, token: String) -> Option<String> {
+        if self.words.contains(&token) {
+            None
+        } else {
+            Some(token)
+        }
+    }
+}
+
+/// Drops tokens shorter than `min` or longer than `max` characters.
+pub struct Length {
+    min: usize,
+    max: usize
+}
+
+impl Length {
+    /// Constructs a new stage keeping tokens from `min` to `max` characters.
+    pub fn new(min: usize, max: usize) -> Self {
+        Length {
+            min,
+            max
+        }
+    }
+}
+
+impl TokenFilter for Length {
+    fn apply(&self, token: String) -> Option<String> {
+        let length = token.chars().count();
+        if length >= self.min && length <= self.max {
+            Some(token)
+        } else {
+            None
+        }
+    }
+}
+
+/// Indexes the bucket of numbers beside them, so that a range of numbers can be searched with a single token.
+///
+/// A token made of ASCII digits only is kept, and followed by the start of its bucket with a prefix and an `s` suffix:
+/// with the prefix `"year:"` and a width of 10, `"2024"` is indexed as `"2024"` and `"year:2020s"`. Other tokens are left unchanged.
+/// Keywords go through the same pipeline, so a bucket token is only searchable if the previous stages keep it as is:
+/// a prefix with punctuation such as `"year:"` cannot be used after [`StripPunctuation`].
+///
+/// # Example
+///
+/// ```
+/// # use index_bloom::analyzer::{Analyzer, Lowercase, NumberBuckets};
+/// let analyzer = Analyzer::new()
+///     .with_filter(Lowercase)
+///     .with_filter(NumberBuckets::new("year:", 10));
+/// assert_eq!(vec!["in", "2024", "year:2020s"], analyzer.analyze("In 2024"));
+/// ```
+pub struct NumberBuckets {
+    prefix: String,
+    width: u64
+}
+
+impl NumberBuckets {
+    /// Constructs a new stage bucketing numbers by ranges of `width`, named with `prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0.
+    pub fn new<S: Into<String>>(prefix: S, width: u64) -> Self {
+        assert!(width > 0, "The width of number buckets must be positive");
+        NumberBuckets {
+            prefix: prefix.into(),
+            width
+        }
+    }
+
+    /// Returns the bucket token of `token`, if it is a number.
+    pub fn bucket(&self, token: &str) -> Option<String> {
+        if token.is_empty() || !token.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let number: u64 = token.parse().ok()?;
+        Some(format!("{}{}s", self.prefix, number - number % self.width))
+    }
+}
+
+impl TokenFilter for NumberBuckets {
+    fn apply(&self, token: String) -> Option<String> {
+        Some(token)
+    }
+
+    fn apply_all(&self, token: String) -> Vec<String> {
+        match self.bucket(&token) {
+            Some(bucket) => vec![token, bucket],
+            None => vec![token]
+        }
+    }
+}
+
+/// Reduces English words to their stem by stripping common suffixes.
+///
+/// This is a light stemmer, much simpler than the Porter algorithm: it handles plurals and the `-ing`, `-ed` and `-ly` suffixes.
+/// It expects lowercase tokens.
+pub struct Stemmer;
+
+impl Stemmer {
+    const MIN_STEM_LENGTH: usize = 3;
+
+    /// Returns the stem of `word`.
+    pub fn stem(word: &str) -> String {
+        let length = word.chars().count();
+        if length <= Self::MIN_STEM_LENGTH {
+            return word.to_string();
+        }
+        if let Some(stem) = word.strip_suffix("ies") {
+            return format!("{}y", stem);
+        }
+        if let Some(stem) = word.strip_suffix("sses") {
+            return format!("{}ss", stem);
+        }
+        if let Some(stem) = word.strip_suffix("es") {
+            if ["s", "x", "z", "ch", "sh"].iter().any(|ending| stem.ends_with(ending)) {
+                return stem.to_string();
+            }
+        }
+        if let Some(stem) = word.strip_suffix('s') {
+            if !stem.ends_with('s') && !stem.ends_with('u') {
+                return stem.to_string();
+            }
+        }
+        for suffix in &["ing", "ed"] {
+            if let Some(stem) = word.strip_suffix(suffix) {
+                if stem.chars().count() >= Self::MIN_STEM_LENGTH {
+                    return Self::undouble(stem);
+                }
+            }
+        }
+        if let Some(stem) = word.strip_suffix("ly") {
+            if stem.chars().count() >= Self::MIN_STEM_LENGTH {
+                return stem.to_string();
+            }
+        }
+        word.to_string()
+    }
+
+    /// Removes the doubled final consonant left by `-ing` and `-ed` suffixes, as in "running".
+    fn undouble(stem: &str) -> String {
+        let mut chars = stem.chars().rev();
+        match (chars.next(), chars.next()) {
+            (Some(last), Some(previous)) if last == previous && !"aeiouls".contains(last) => {
+                stem[..stem.len() - last.len_utf8()].to_string()
+            },
+            _ => stem.to_string()
+        }
+    }
+}
+
+impl TokenFilter for Stemmer {
+    fn apply(&self, token: String) -> Option<String> {
+        Some(Stemmer::stem(&token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_pipeline_splits_whitespace() {
+        let analyzer = Analyzer::new();
+        assert_eq!(vec!["The", "Quick,", "fox"], analyzer.analyze("The  Quick,\tfox"));
+        assert!(analyzer.analyze("").is_empty());
+    }
+
+    #[test]
+    fn default_pipeline_matches_tokenizer() {
+        let analyzer = Analyzer::default();
+        let sentence = "WORD1 (word2), éèêàïùç __ ?";
+        assert_eq!(tokens::Tokens::new(sentence).collect::<Vec<String>>(), analyzer.analyze(sentence));
+    }
+
+    #[test]
+    fn custom_pipeline() {
+        let analyzer = Analyzer::new()
+            .with_filter(Lowercase)
+            .with_filter(StopWords::new(vec!["the", "is"]))
+            .with_filter(Length::new(4, 6));
+        assert_eq!(vec!["quick", "brown", "over"], analyzer.analyze("The Quick brown fox is JUMPING over"));
+    }
+
+    #[test]
+    fn pipeline_order_matters() {
+        let analyzer = Analyzer::from_filters(vec![Box::new(StopWords::new(vec!["the"])), Box::new(Lowercase)]);
+        assert_eq!(vec!["the"], analyzer.analyze("The the"));
+    }
+
+    #[test]
+    fn bucket_numbers() {
+        let decades = NumberBuckets::new("year:", 10);
+        assert_eq!(Some("year:2020s".to_string()), decades.bucket("2024"));
+        assert_eq!(Some("year:2020s".to_string()), decades.bucket("2020"));
+        assert_eq!(Some("year:0s".to_string()), decades.bucket("7"));
+        assert_eq!(None, decades.bucket("2024a"));
+        assert_eq!(None, decades.bucket("-5"));
+        assert_eq!(None, decades.bucket("99999999999999999999999"));
+        let analyzer = Analyzer::new().with_filter(NumberBuckets::new("", 100)).with_filter(Lowercase);
+        assert_eq!(vec!["born", "1984", "1900s"], analyzer.analyze("Born 1984"));
+    }
+
+    #[test]
+    fn stem_words() {
+        assert_eq!("apple", Stemmer::stem("apples"));
+        assert_eq!("apple", Stemmer::stem("apple"));
+        assert_eq!("pony", Stemmer::stem("ponies"));
+        assert_eq!("class", Stemmer::stem("classes"));
+        assert_eq!("box", Stemmer::stem("boxes"));
+        assert_eq!("church", Stemmer::stem("churches"));
+        assert_eq!("glass", Stemmer::stem("glass"));
+        assert_eq!("status", Stemmer::stem("status"));
+        assert_eq!("jump", Stemmer::stem("jumping"));
+        assert_eq!("run", Stemmer::stem("running"));
+        assert_eq!("stop", Stemmer::stem("stopped"));
+        assert_eq!("fall", Stemmer::stem("falling"));
+        assert_eq!("quick", Stemmer::stem("quickly"));
+        assert_eq!("bed", Stemmer::stem("bed"));
+        assert_eq!("sing", Stemmer::stem("sing"));
+    }
+}
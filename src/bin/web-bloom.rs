@@ -0,0 +1,134 @@
+//! Command line interface to build, dump, restore and search an `index-bloom` index.
+//!
+//! ```text
+//! web-bloom [--error-rate <rate>] [--restore <dump>] [--index <file>]... [--dump <dump>] [--search <query>] [--format text|json]
+//! ```
+//!
+//! Files are ingested under their path. Matching documents are printed one per line, or as a JSON
+//! array of objects with `--format json`, and the exit status is 1 when no document matches.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::process;
+use serde::Serialize;
+
+use index_bloom::{Error, Index};
+
+const USAGE: &str = "Usage: web-bloom [--error-rate <rate>] [--restore <dump>] [--index <file>]... [--dump <dump>] [--search <query>] [--format text|json]";
+const DEFAULT_ERROR_RATE: f32 = 0.00001;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Format {
+    #[default]
+    Text,
+    Json
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    error_rate: Option<f32>,
+    restore: Option<String>,
+    index: Vec<String>,
+    dump: Option<String>,
+    search: Option<String>,
+    format: Format
+}
+
+/// A matching document, as printed with `--format json`.
+#[derive(Serialize)]
+struct SearchHit<'a> {
+    name: &'a str,
+    false_positive_rate: f64
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--error-rate" => {
+                    let rate = value()?;
+                    options.error_rate = Some(rate.parse().map_err(|_| format!("Invalid error rate {}", rate))?);
+                },
+                "--restore" => options.restore = Some(value()?),
+                "--index" => options.index.push(value()?),
+                "--dump" => options.dump = Some(value()?),
+                "--search" => options.search = Some(value()?),
+                "--format" => {
+                    options.format = match value()?.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        format => return Err(format!("Unknown format {}", format))
+                    };
+                },
+                _ => return Err(format!("Unknown argument {}", arg))
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(options) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    }
+}
+
+/// Runs the command, returning whether the search, if any, matched a document.
+fn run(options: Options) -> Result<bool, String> {
+    let mut index = match &options.restore {
+        Some(path) => {
+            let content = fs::read_to_string(path).map_err(|error| format!("Unable to read dump file {} : {}", path, error))?;
+            Index::try_restore(&content).map_err(|error| format!("Unable to restore dump file {} : {}", path, error))?
+        },
+        None => Index::new(options.error_rate.unwrap_or(DEFAULT_ERROR_RATE))
+    };
+    for path in &options.index {
+        index.ingest_file(path.clone(), path).map_err(|error| format!("Unable to index {} : {}", path, error))?;
+    }
+    if let Some(path) = &options.dump {
+        index.dump_to_file(path).map_err(|error| format!("Unable to write dump file {} : {}", path, error))?;
+    }
+    match &options.search {
+        Some(query) => {
+            let hits = search(&index, query).map_err(|error| format!("Unable to search {} : {}", query, error))?;
+            match options.format {
+                Format::Text => {
+                    for name in &hits {
+                        println!("{}", name);
+                    }
+                },
+                Format::Json => {
+                    let false_positive_rates: HashMap<&String, f64> = index.iter()
+                        .map(|(name, stats)| (name, stats.false_positive_rate))
+                        .collect();
+                    let json_hits: Vec<SearchHit> = hits.iter()
+                        .map(|name| SearchHit { name, false_positive_rate: false_positive_rates[name] })
+                        .collect();
+                    let content = serde_json::to_string(&json_hits).map_err(|error| format!("Unable to serialize results : {}", error))?;
+                    println!("{}", content);
+                }
+            }
+            Ok(!hits.is_empty())
+        },
+        None => Ok(true)
+    }
+}
+
+fn search<'a>(index: &'a Index, query: &str) -> Result<Vec<&'a String>, Error> {
+    Ok(index.search(query)?.unwrap_or_default())
+}
@@ -1,79 +1,887 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
 use std::str;
 use std::cell::RefCell;
-use std::convert::TryFrom;
 use blake2::VarBlake2b;
 use blake2::digest::{Update, VariableOutput};
-use serde::{Serialize, Deserialize};
-use crate::errors::Error;
+use serde::{Serialize, Serializer, Deserialize};
+use crate::errors::{Error, Warning};
 
-#[derive(Serialize, Deserialize)]
+/// A Bloom filter storing the words of a document.
+///
+/// A Bloom filter tells whether a word is absent for sure, or present with a probability of false positive.
+///
+/// A filter with very few bits set, such as a filter sized for much more words than it holds, is serialized as the list of its set bits rather than its whole bitfield.
+/// Both forms are deserialized transparently.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "DeserializedFilter")]
 pub struct BloomFilter {
     key_size: u32,
     bitfield: Vec<u8>,
-    bitfield_size: usize
+    bitfield_size: usize,
+    terms: Option<BTreeSet<String>>,
+    bit_order: BitOrder,
+    inserted: bool,
+    digest: Digest
+}
+
+/// The digest hashing the words of a [`BloomFilter`].
+#[derive(Debug, Clone, Copy)]
+enum Digest {
+    /// The Blake2b digest, computed without any allocation.
+    Blake2b,
+    /// Another digest, for instance to check the self test against a broken digest.
+    Custom(fn(&[u8]) -> Vec<u8>)
+}
+
+impl Digest {
+    fn function(&self) -> fn(&[u8]) -> Vec<u8> {
+        match self {
+            Digest::Blake2b => blake2b_digest,
+            Digest::Custom(digest) => *digest
+        }
+    }
+
+    /// Returns the hash of `key` repeated `repetitions` times, the same as the hashes of `word_hashes_with`.
+    fn hash(&self, key: &str, repetitions: u32) -> Result<usize, Error> {
+        match self {
+            Digest::Blake2b => Ok(blake2b_hash(key, repetitions)),
+            Digest::Custom(digest) => parse_digest(&digest(key.repeat(repetitions as usize).as_bytes()))
+        }
+    }
+}
+
+/// The number of hashes of a filter sized for a probability of false positive of 1e-9, the most worth hashing each word for.
+///
+/// The cost of each hash grows with its rank, so a filter much larger than its words would otherwise take forever to insert a word.
+const MAX_KEY_SIZE: u32 = 30;
+
+/// The fill ratio below which a filter is serialized as the list of its set bits.
+const SPARSE_FILL_RATIO: f64 = 1.0 / 32.0;
+
+/// The serialized form of a [`BloomFilter`], with either its whole bitfield or the positions of its set bits.
+#[derive(Serialize)]
+struct SerializedFilter<'a> {
+    key_size: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bitfield: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    set_bits: Option<Vec<usize>>,
+    bitfield_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    terms: Option<&'a BTreeSet<String>>,
+    #[serde(skip_serializing_if = "BitOrder::is_lsb")]
+    bit_order: BitOrder
+}
+
+#[derive(Deserialize)]
+struct DeserializedFilter {
+    key_size: u32,
+    #[serde(default)]
+    bitfield: Option<Vec<u8>>,
+    #[serde(default)]
+    set_bits: Option<Vec<usize>>,
+    bitfield_size: usize,
+    #[serde(default)]
+    terms: Option<BTreeSet<String>>,
+    #[serde(default)]
+    bit_order: BitOrder
+}
+
+/// The order of bits within each byte of the bitfield of a [`BloomFilter`].
+///
+/// The bit at position `p` of a filter is in byte `p / 8`; the bit order tells which bit of this byte it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitOrder {
+    /// Position `p % 8` is the `p % 8`-th least significant bit.
+    #[default]
+    Lsb,
+    /// Position `p % 8` is the `p % 8`-th most significant bit, as in many other implementations.
+    Msb
+}
+
+impl BitOrder {
+    fn is_lsb(&self) -> bool {
+        *self == BitOrder::Lsb
+    }
 }
 
 impl BloomFilter {
+    /// The fill ratio above which an insertion raises a [`Warning::Saturated`].
+    ///
+    /// A filter filled up to its capacity has a fill ratio around 0.5.
+    pub const FILL_RATIO_WARNING: f64 = 0.75;
+
+    /// Constructs a new, empty `BloomFilter` sized to hold `capacity` words with a probability `err_rate` of false positive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// let filter = BloomFilter::new(100, 0.001);
+    /// ```
     pub fn new(capacity: usize, err_rate: f32) -> Self {
         if capacity == 0 {
             panic!("Invalid Bloom filter capacity: cannot be 0");
         }
         let capacity_float = capacity as f32;
-        let factor = (1.0/2.0_f32.powf(2.0_f32.ln())).ln();
-        let bitfield_size = ((capacity_float * err_rate.ln()) / factor).ceil();
+        let bitfield_size = Self::optimal_bitfield_size(capacity, err_rate) as f32;
         let key_size = ((bitfield_size / capacity_float) * 2.0_f32.ln()).ceil() as u32;
-        let mut bitfield = Vec::with_capacity((bitfield_size / 8.0).ceil() as usize);
-        for _ in 0..(bitfield.capacity()) {
-            bitfield.push(0);
-        }
+        let bitfield = vec![0; (bitfield_size / 8.0).ceil() as usize];
         BloomFilter {
             key_size,
             bitfield,
-            bitfield_size: bitfield_size as usize
+            bitfield_size: bitfield_size as usize,
+            terms: None,
+            bit_order: BitOrder::Lsb,
+            inserted: false,
+            digest: Digest::Blake2b
+        }
+    }
+
+    /// Returns the number of bits of a new filter of the given `capacity` and `err_rate`, without allocating it.
+    pub(crate) fn optimal_bitfield_size(capacity: usize, err_rate: f32) -> usize {
+        let factor = (1.0/2.0_f32.powf(2.0_f32.ln())).ln();
+        ((capacity as f32 * err_rate.ln()) / factor).ceil() as usize
+    }
+
+    /// Constructs a new, empty `BloomFilter` of `bitfield_size` bits, with the best number of hashes for `capacity` words, up to [`MAX_KEY_SIZE`].
+    ///
+    /// The probability of false positive depends on the given size rather than on an error rate.
+    pub(crate) fn with_bitfield_size(capacity: usize, bitfield_size: usize) -> Self {
+        let key_size = ((bitfield_size as f32 / capacity.max(1) as f32) * 2.0_f32.ln()).ceil().clamp(1.0, MAX_KEY_SIZE as f32) as u32;
+        BloomFilter {
+            key_size,
+            bitfield: vec![0; bitfield_size.div_ceil(8)],
+            bitfield_size,
+            terms: None,
+            bit_order: BitOrder::Lsb,
+            inserted: false,
+            digest: Digest::Blake2b
         }
     }
 
+    /// Constructs a new, empty `BloomFilter` using at most `max_bytes` bytes for `expected_terms` words, returning it along with its probability of false positive.
+    ///
+    /// This is the reverse of [`BloomFilter::new`]: the size of the bitfield is given, and the error rate follows from it.
+    /// The filter uses the best number of hashes for the expected number of words, up to the number of hashes of a probability of false positive of 1e-9,
+    /// and the returned probability holds once they are all inserted. With 0 expected words, the filter is built as for 1 word and the returned probability is 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the budget is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// let (filter, error_rate) = BloomFilter::with_memory_budget(1000, 1024);
+    /// println!("{} bits, {:.4} probability of false positive", filter.bitfield_size(), error_rate);
+    /// ```
+    pub fn with_memory_budget(expected_terms: usize, max_bytes: usize) -> (Self, f64) {
+        if max_bytes == 0 {
+            panic!("Invalid Bloom filter memory budget: cannot be 0");
+        }
+        let filter = BloomFilter::with_bitfield_size(expected_terms, max_bytes * 8);
+        let error_rate = filter.expected_false_positive_rate(expected_terms);
+        (filter, error_rate)
+    }
+
+    /// Returns the probability of false positive of the filter once it holds `terms` words, given its geometry.
+    fn expected_false_positive_rate(&self, terms: usize) -> f64 {
+        let key_size = f64::from(self.key_size);
+        (1.0 - (-key_size * terms as f64 / self.bitfield_size as f64).exp()).powf(key_size)
+    }
+
+    /// Constructs a new, empty `BloomFilter` which also retains the inserted words.
+    ///
+    /// Retaining words costs memory but allows to rebuild the filter with a new geometry, for instance to grow it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// let filter = BloomFilter::with_terms(100, 0.001);
+    /// assert!(filter.retains_terms());
+    /// ```
+    pub fn with_terms(capacity: usize, err_rate: f32) -> Self {
+        BloomFilter::new(capacity, err_rate).with_empty_terms()
+    }
+
+    /// Retain the words inserted from now on, the filter being empty.
+    pub(crate) fn with_empty_terms(mut self) -> Self {
+        self.terms = Some(BTreeSet::new());
+        self
+    }
+
+    /// Constructs a `BloomFilter` from its geometry and an existing bitfield, for instance read from a binary dump.
+    ///
+    /// The bits are used as is, only the length of the bitfield is checked against `bitfield_size`.
+    ///
+    /// # Errors
+    ///
+    /// If `bitfield_size` is 0 or the bitfield does not hold exactly `bitfield_size` bits rounded up to a whole byte, an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn restore_filter() -> Result<(), Error> {
+    /// let filter = BloomFilter::from_parts(4, 24, vec![8, 130, 65])?;
+    /// assert_eq!(24, filter.bitfield_size());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_parts(key_size: u32, bitfield_size: usize, bitfield: Vec<u8>) -> Result<Self, Error> {
+        if bitfield_size == 0 || bitfield.len() != bitfield_size.div_ceil(8) {
+            return Err(Error::InvalidBitfield);
+        }
+        Ok(BloomFilter {
+            key_size,
+            bitfield,
+            bitfield_size,
+            terms: None,
+            bit_order: BitOrder::Lsb,
+            inserted: false,
+            digest: Digest::Blake2b
+        })
+    }
+
+    /// Hash the words with `digest` rather than Blake2b, which makes the filter incompatible with any other.
+    pub(crate) fn with_digest(mut self, digest: fn(&[u8]) -> Vec<u8>) -> Self {
+        self.digest = Digest::Custom(digest);
+        self
+    }
+
+    /// Interpret the bitfield in the given bit order, for instance to read a bitfield produced by another implementation (see [`BloomFilter::from_parts`]).
+    ///
+    /// The bits already set are not moved: the bit order should be chosen before inserting words.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{BitOrder, BloomFilter};
+    /// let filter = BloomFilter::new(100, 0.001).with_bit_order(BitOrder::Msb);
+    /// assert_eq!(BitOrder::Msb, filter.bit_order());
+    /// ```
+    pub fn with_bit_order(mut self, bit_order: BitOrder) -> Self {
+        self.bit_order = bit_order;
+        self
+    }
+
+    /// Returns the order of bits within each byte of the bitfield.
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    /// Returns whether the filter retains the inserted words (see [`BloomFilter::with_terms`]).
+    pub fn retains_terms(&self) -> bool {
+        self.terms.is_some()
+    }
+
+    /// Returns the inserted words if the filter retains them.
+    pub fn terms(&self) -> Option<&BTreeSet<String>> {
+        self.terms.as_ref()
+    }
+
+    /// Insert a word in the filter.
+    ///
+    /// If the fill ratio of the filter exceeds [`BloomFilter::FILL_RATIO_WARNING`] after the insertion, a warning is available from [`BloomFilter::last_warning`].
+    ///
+    /// # Errors
+    ///
+    /// If the word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn insert_word() -> Result<(), Error> {
+    /// let mut filter = BloomFilter::new(100, 0.001);
+    /// filter.insert("word")?;
+    /// assert!(filter.contains("word")?);
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn insert(&mut self, key: &str) -> Result<(), Error> {
         let positions = self.hash_word(key)?;
-        for position in positions {
-            let array_index = position / 8;
-            let bit_index = position % 8;
-            self.bitfield[array_index] |= (2u8).pow(u32::try_from(bit_index).unwrap());
+        self.set_bits(&positions);
+        if let Some(terms) = &mut self.terms {
+            terms.insert(key.to_string());
         }
+        self.inserted = true;
         Ok(())
     }
 
+    /// Check whether a word may be present in the filter.
+    ///
+    /// A negative answer is certain, a positive one may be a false positive.
+    ///
+    /// # Errors
+    ///
+    /// If the word cannot be hashed then an error is returned.
     pub fn contains(&self, key: &str) -> Result<bool, Error> {
-        let positions = self.hash_word(key)?;
-        Ok(positions.into_iter().all(|position| {
-            let array_index = position / 8;
-            let bit_index = u8::try_from(position % 8).unwrap();
-            let mask = (2u8).pow(u32::try_from(bit_index).unwrap());
-            self.bitfield[array_index] & mask == mask
-        }))
+        let mut probes = 0;
+        self.probe(key, &mut probes)
     }
 
-    fn hash_word(&self, key: &str) -> Result<Vec<usize>, Error> {
-        let mut result = Vec::new();
-        let mut keys_buffer = Vec::new();
-        for _ in 0..self.key_size {
-            keys_buffer.push(key.to_string());
-            let mut hasher = VarBlake2b::new(4).unwrap();
-            let k = keys_buffer.join("");
-            hasher.update(&k);
-            let digest_vec: RefCell<Vec<u8>> = RefCell::new(vec![]);
-            hasher.finalize_variable(|digest| {
-                *digest_vec.borrow_mut() = digest.to_vec();
+    /// Same as `contains`, adding the number of bits tested to `probes`.
+    ///
+    /// Positions are computed one at a time, stopping at the first unset bit, without any allocation.
+    pub(crate) fn probe(&self, key: &str, probes: &mut usize) -> Result<bool, Error> {
+        for repetitions in 1..=self.key_size {
+            *probes += 1;
+            if !self.is_set(self.digest.hash(key, repetitions)? % self.bitfield_size) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Check whether none of the words may be present in the filter, stopping at the first word which may be present.
+    ///
+    /// This is the building block of exclusion searches. A negative answer may be due to a false positive on one of the words,
+    /// so a document may be wrongly excluded, while a positive answer is certain.
+    ///
+    /// # Errors
+    ///
+    /// If a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn exclude_words() -> Result<(), Error> {
+    /// let mut filter = BloomFilter::new(1, 0.00001);
+    /// filter.insert("hello")?;
+    /// assert!(filter.probably_contains_none(&["world".to_string()])?);
+    /// assert!(!filter.probably_contains_none(&["world".to_string(), "hello".to_string()])?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn probably_contains_none(&self, terms: &[String]) -> Result<bool, Error> {
+        Ok(!self.contains_any(terms)?)
+    }
+
+    pub(crate) fn contains_all(&self, keys: &[String]) -> Result<bool, Error> {
+        for key in keys {
+            if !self.contains(key)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn contains_any(&self, keys: &[String]) -> Result<bool, Error> {
+        for key in keys {
+            if self.contains(key)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Check the consistency of the filter, for instance after it was restored from an untrusted dump.
+    ///
+    /// The bitfield must hold exactly `bitfield_size` bits rounded up to a whole byte, without any bit set beyond them,
+    /// each word must be hashed at least once, and the retained words, if any, must be present.
+    ///
+    /// # Errors
+    ///
+    /// If the bitfield does not match the size of the filter, [`Error::InvalidBitfield`] is returned.
+    /// If the filter is inconsistent otherwise, [`Error::CorruptedFilter`] is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn restore_filter() -> Result<(), Error> {
+    /// let filter = BloomFilter::from_parts(4, 20, vec![8, 130, 16])?;
+    /// assert!(filter.verify().is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.bitfield_size == 0 || self.bitfield.len() != self.bitfield_size.div_ceil(8) {
+            return Err(Error::InvalidBitfield);
+        }
+        let padding_set = (self.bitfield_size..self.bitfield.len() * 8).any(|position| self.is_set(position));
+        if padding_set || self.key_size == 0 {
+            return Err(Error::CorruptedFilter);
+        }
+        if let Some(terms) = &self.terms {
+            for term in terms {
+                if !self.contains(term)? {
+                    return Err(Error::CorruptedFilter);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the implementation behaves as expected on known vectors.
+    ///
+    /// Builds a filter with a fixed geometry, inserts a fixed set of words and compares its bits with the expected ones.
+    /// Also checks that the inserted words are found and that a known absent word is not.
+    /// A failure means filters built by this implementation would not be compatible with existing dumps, for instance after a change of the hash function.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// assert!(BloomFilter::self_test());
+    /// ```
+    pub fn self_test() -> bool {
+        Self::self_test_with(blake2b_digest)
+    }
+
+    /// Returns the number of hashes computed for each word.
+    pub fn key_size(&self) -> u32 {
+        self.key_size
+    }
+
+    /// Returns the number of bits of the filter.
+    pub fn bitfield_size(&self) -> usize {
+        self.bitfield_size
+    }
+
+    /// Returns the number of bits set in the filter, 0 for a filter in which nothing was inserted.
+    pub fn bits_set(&self) -> usize {
+        self.bitfield.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    /// Returns the ratio of bits set in the filter, between 0 and 1.
+    pub fn fill_ratio(&self) -> f64 {
+        self.bits_set() as f64 / self.bitfield_size as f64
+    }
+
+    /// Returns the estimated probability of false positive given the current fill ratio of the filter.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.key_size as i32)
+    }
+
+    /// Returns the estimated number of unique words inserted, given the number of bits set.
+    ///
+    /// A filter with every bit set cannot be estimated and returns `usize::MAX`.
+    /// This tells how full the filter is: an estimate well above the capacity the filter was created for means its false positive rate has degraded.
+    pub fn estimated_count(&self) -> usize {
+        let bitfield_size = self.bitfield_size as f64;
+        let estimate = -bitfield_size / f64::from(self.key_size) * (1.0 - self.bits_set() as f64 / bitfield_size).ln();
+        estimate.round() as usize
+    }
+
+    /// Returns how uniformly the set bits are spread over the filter, between 0 and 1.
+    ///
+    /// The bits set in each byte are compared to the binomial distribution expected from a uniform hash with a chi-square statistic.
+    /// The score is 1 while the statistic stays within three standard deviations of its expected value, then decreases in inverse proportion
+    /// to its deviation: a healthy filter scores 1, while clustered bits, for instance due to a broken hash, give a very low score.
+    /// Empty, full or single-byte filters score 1 as their bits carry no information about the hash.
+    pub fn distribution_score(&self) -> f64 {
+        let fill_ratio = self.fill_ratio();
+        let buckets = self.bitfield.len();
+        if buckets < 2 || fill_ratio <= 0.0 || fill_ratio >= 1.0 {
+            return 1.0;
+        }
+        let chi_square: f64 = self.bitfield.iter().enumerate().map(|(index, byte)| {
+            let bits = (self.bitfield_size - index * 8).min(8) as f64;
+            let expected = bits * fill_ratio;
+            let deviation = f64::from(byte.count_ones()) - expected;
+            deviation * deviation / (expected * (1.0 - fill_ratio))
+        }).sum();
+        let degrees_of_freedom = (buckets - 1) as f64;
+        let deviations = (chi_square - degrees_of_freedom) / (2.0 * degrees_of_freedom).sqrt();
+        if deviations <= 3.0 {
+            1.0
+        } else {
+            3.0 / deviations
+        }
+    }
+
+    /// Check whether `other` has the same geometry, so that both filters can be combined bit by bit.
+    ///
+    /// Filters are compatible when they have the same number of hashes, number of bits and bit order.
+    /// Every filter uses the same unseeded hash function, so it never makes filters incompatible.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{BitOrder, BloomFilter};
+    /// let filter = BloomFilter::new(100, 0.001);
+    /// assert!(filter.is_compatible_with(&BloomFilter::with_terms(100, 0.001)));
+    /// assert!(!filter.is_compatible_with(&BloomFilter::new(200, 0.001)));
+    /// assert!(!filter.is_compatible_with(&BloomFilter::new(100, 0.001).with_bit_order(BitOrder::Msb)));
+    /// ```
+    pub fn is_compatible_with(&self, other: &BloomFilter) -> bool {
+        self.key_size == other.key_size && self.bitfield_size == other.bitfield_size && self.bit_order == other.bit_order
+    }
+
+    /// Insert every word of `other` in the filter, by merging their bits.
+    ///
+    /// The filter then contains every word of either filter, for instance to merge the filters of several shards.
+    /// It keeps retaining its words only if `other` retains its words too, as the words of `other` would be lost by a rebuild otherwise.
+    ///
+    /// # Errors
+    ///
+    /// If the filters are not compatible (see [`BloomFilter::is_compatible_with`]) then an error is returned and the filter is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn merge_filters() -> Result<(), Error> {
+    /// let mut filter = BloomFilter::new(100, 0.001);
+    /// let mut other = BloomFilter::new(100, 0.001);
+    /// filter.insert("foo")?;
+    /// other.insert("bar")?;
+    /// filter.union(&other)?;
+    /// assert!(filter.contains("foo")? && filter.contains("bar")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn union(&mut self, other: &BloomFilter) -> Result<(), Error> {
+        if !self.is_compatible_with(other) {
+            return Err(Error::IncompatibleFilter);
+        }
+        for (byte, other_byte) in self.bitfield.iter_mut().zip(&other.bitfield) {
+            *byte |= other_byte;
+        }
+        self.terms = match (self.terms.take(), &other.terms) {
+            (Some(mut terms), Some(other_terms)) => {
+                terms.extend(other_terms.iter().cloned());
+                Some(terms)
+            },
+            _ => None
+        };
+        self.inserted = true;
+        Ok(())
+    }
+
+    /// Check whether `other` likely holds the same words, up to a `tolerance` between 0 and 1.
+    ///
+    /// The similarity of the filters is estimated by the Jaccard index of their bits, the number of bits set in both filters divided
+    /// by the number of bits set in any of them. Filters are approximately equal when the similarity is at least `1 - tolerance`: a `tolerance` of 0 requires the same bits.
+    /// Two empty filters are equal.
+    ///
+    /// # Errors
+    ///
+    /// If the filters are not compatible (see [`BloomFilter::is_compatible_with`]) then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn compare_filters() -> Result<(), Error> {
+    /// let mut filter = BloomFilter::new(100, 0.001);
+    /// let mut other = BloomFilter::new(100, 0.001);
+    /// filter.insert("word")?;
+    /// other.insert("word")?;
+    /// assert!(filter.approximately_equals(&other, 0.1)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn approximately_equals(&self, other: &BloomFilter, tolerance: f64) -> Result<bool, Error> {
+        if !self.is_compatible_with(other) {
+            return Err(Error::IncompatibleFilter);
+        }
+        let (common_bits, any_bits) = self.bitfield.iter().zip(&other.bitfield)
+            .fold((0, 0), |(common_bits, any_bits), (byte, other_byte)| {
+                (common_bits + (byte & other_byte).count_ones(), any_bits + (byte | other_byte).count_ones())
             });
-            let byte = digest_vec.into_inner().iter().map(|d| format!("{:x}", d)).collect::<Vec<String>>().join("");
-            let position = match usize::from_str_radix(&byte, 16) {
-                Ok(num) => num % self.bitfield_size,
-                Err(error) => return Err(Error::HashWord(error))
-            };
-            result.push(position);
+        if any_bits == 0 {
+            return Ok(true);
+        }
+        let similarity = f64::from(common_bits) / f64::from(any_bits);
+        Ok(similarity >= 1.0 - tolerance)
+    }
+
+    /// Returns the warning raised by the last insertions, if any.
+    ///
+    /// The fill ratio is only computed when the warning is requested, so that insertions do not count the bits set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{BloomFilter, Warning};
+    /// # use index_bloom::Error;
+    /// # fn insert_word() -> Result<(), Error> {
+    /// let mut filter = BloomFilter::new(1, 0.1);
+    /// for word in &["one", "two", "three", "four", "five"] {
+    ///     filter.insert(word)?;
+    /// }
+    /// if let Some(Warning::Saturated { fill_ratio }) = filter.last_warning() {
+    ///     println!("The filter is saturated ({} bits set)", fill_ratio);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last_warning(&self) -> Option<Warning> {
+        if !self.inserted {
+            return None;
+        }
+        let fill_ratio = self.fill_ratio();
+        if fill_ratio > Self::FILL_RATIO_WARNING {
+            Some(Warning::Saturated { fill_ratio })
+        } else {
+            None
+        }
+    }
+
+    /// Rebuild a term-retaining filter with the geometry of a new filter of the given `capacity` and `err_rate`.
+    ///
+    /// Every retained word is inserted again, so the filter still contains the same words with a new probability of false positive.
+    /// A capacity of 0 is treated as 1.
+    ///
+    /// # Errors
+    ///
+    /// If the filter does not retain its words (see [`BloomFilter::with_terms`]) or a word cannot be hashed, an error is returned and the filter is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::BloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn resize_filter() -> Result<(), Error> {
+    /// let mut filter = BloomFilter::with_terms(2, 0.1);
+    /// filter.insert("word")?;
+    /// filter.resize(100, 0.001)?;
+    /// assert!(filter.contains("word")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize(&mut self, capacity: usize, err_rate: f32) -> Result<(), Error> {
+        let terms = match &self.terms {
+            Some(terms) => terms,
+            None => return Err(Error::TermsNotRetained)
+        };
+        let mut filter = BloomFilter::with_terms(capacity.max(1), err_rate).with_bit_order(self.bit_order);
+        for term in terms {
+            filter.insert(term)?;
+        }
+        *self = filter;
+        Ok(())
+    }
+
+    /// Rebuilds the filter with only the retained terms for which `keep` returns `true`, sized for the remaining terms.
+    pub(crate) fn retain_terms<F: FnMut(&String) -> bool>(&mut self, keep: F, err_rate: f32) -> Result<(), Error> {
+        let terms = match &mut self.terms {
+            Some(terms) => terms,
+            None => return Err(Error::TermsNotRetained)
+        };
+        terms.retain(keep);
+        let capacity = terms.len();
+        self.resize(capacity, err_rate)
+    }
+
+    /// Sets the bits and retains the terms of `other`, which must have the same geometry.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn merge(&mut self, other: BloomFilter) {
+        debug_assert!(self.is_compatible_with(&other));
+        for (byte, other_byte) in self.bitfield.iter_mut().zip(other.bitfield) {
+            *byte |= other_byte;
+        }
+        if let (Some(terms), Some(other_terms)) = (&mut self.terms, other.terms) {
+            terms.extend(other_terms);
+        }
+        self.inserted = true;
+    }
+
+    pub(crate) fn heap_usage(&self) -> usize {
+        self.bitfield.capacity()
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.bitfield.shrink_to_fit();
+    }
+
+    fn hash_word(&self, key: &str) -> Result<Vec<usize>, Error> {
+        let hashes = self.key_hashes(key, self.key_size)?;
+        Ok(hashes.into_iter().map(|hash| hash % self.bitfield_size).collect())
+    }
+
+    /// Returns the first `count` hashes of a word, independent of the geometry of any filter, to be tested with `contains_hashes`.
+    pub(crate) fn word_hashes(key: &str, count: u32) -> Result<Vec<usize>, Error> {
+        word_hashes_with(key, count, blake2b_digest)
+    }
+
+    /// Same as `word_hashes`, with the digest of the filter.
+    pub(crate) fn key_hashes(&self, key: &str, count: u32) -> Result<Vec<usize>, Error> {
+        word_hashes_with(key, count, self.digest.function())
+    }
+
+    /// Same as `contains` for a word hashed beforehand with `word_hashes`, with at least `key_size` hashes.
+    pub(crate) fn contains_hashes(&self, hashes: &[usize]) -> bool {
+        debug_assert!(hashes.len() >= self.key_size as usize);
+        hashes.iter()
+            .take(self.key_size as usize)
+            .all(|hash| self.is_set(hash % self.bitfield_size))
+    }
+
+    fn is_set(&self, position: usize) -> bool {
+        let (array_index, mask) = bit_location(position, self.bit_order);
+        self.bitfield[array_index] & mask == mask
+    }
+
+    fn set_bits(&mut self, positions: &[usize]) {
+        for position in positions {
+            let (array_index, mask) = bit_location(*position, self.bit_order);
+            self.bitfield[array_index] |= mask;
         }
-        Ok(result)
     }
+
+    fn self_test_with(digest: fn(&[u8]) -> Vec<u8>) -> bool {
+        let mut filter = BloomFilter::new(SELF_TEST_WORDS.len(), SELF_TEST_ERROR_RATE).with_digest(digest);
+        let mut words_positions = Vec::new();
+        for word in &SELF_TEST_WORDS {
+            match filter.hash_word(word) {
+                Ok(positions) => {
+                    filter.set_bits(&positions);
+                    words_positions.push(positions);
+                },
+                Err(_) => return false
+            }
+        }
+        let is_set = |position: &usize| filter.is_set(*position);
+        let absent_word_found = match filter.hash_word(SELF_TEST_ABSENT_WORD) {
+            Ok(positions) => positions.iter().all(is_set),
+            Err(_) => return false
+        };
+        filter.bitfield == SELF_TEST_BITFIELD
+            && words_positions.iter().all(|positions| positions.iter().all(is_set))
+            && !absent_word_found
+    }
+}
+
+/// The words inserted by [`BloomFilter::self_test`].
+const SELF_TEST_WORDS: [&str; 4] = ["hello", "world", "bloom", "filter"];
+/// The word [`BloomFilter::self_test`] expects to be absent from its filter.
+const SELF_TEST_ABSENT_WORD: &str = "index";
+const SELF_TEST_ERROR_RATE: f32 = 0.01;
+/// The bitfield expected once [`SELF_TEST_WORDS`] are inserted.
+const SELF_TEST_BITFIELD: [u8; 5] = [15, 222, 40, 206, 60];
+
+/// Returns the `count` hashes of `key` with the given `digest`, the i-th hash being the digest of the key repeated i + 1 times.
+fn word_hashes_with(key: &str, count: u32, digest: fn(&[u8]) -> Vec<u8>) -> Result<Vec<usize>, Error> {
+    let mut result = Vec::new();
+    let mut keys_buffer = Vec::new();
+    for _ in 0..count {
+        keys_buffer.push(key.to_string());
+        let k = keys_buffer.join("");
+        result.push(parse_digest(&digest(k.as_bytes()))?);
+    }
+    Ok(result)
+}
+
+/// Returns the hash of a word given its `digest`, which must not be empty.
+fn parse_digest(digest: &[u8]) -> Result<usize, Error> {
+    let byte = digest.iter().map(|d| format!("{:x}", d)).collect::<Vec<String>>().join("");
+    usize::from_str_radix(&byte, 16).map_err(Error::HashWord)
+}
+
+/// Same as the hashes of `word_hashes_with` with the Blake2b digest, for `key` repeated `repetitions` times, without any allocation.
+fn blake2b_hash(key: &str, repetitions: u32) -> usize {
+    let mut hasher = VarBlake2b::new(4).unwrap();
+    for _ in 0..repetitions {
+        hasher.update(key.as_bytes());
+    }
+    let mut hash = 0;
+    hasher.finalize_variable(|digest| {
+        // Same value as parsing the bytes formatted in hexadecimal without padding
+        hash = digest.iter().fold(0, |hash, byte| {
+            let shift = if *byte < 0x10 { 4 } else { 8 };
+            (hash << shift) | usize::from(*byte)
+        });
+    });
+    hash
+}
+
+/// Returns the 4 bytes Blake2b digest of `data`.
+pub(crate) fn blake2b_digest(data: &[u8]) -> Vec<u8> {
+    let mut hasher = VarBlake2b::new(4).unwrap();
+    hasher.update(data);
+    let digest_vec: RefCell<Vec<u8>> = RefCell::new(vec![]);
+    hasher.finalize_variable(|digest| {
+        *digest_vec.borrow_mut() = digest.to_vec();
+    });
+    digest_vec.into_inner()
+}
+
+impl Serialize for BloomFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sparse = self.fill_ratio() < SPARSE_FILL_RATIO;
+        let set_bits = if sparse {
+            Some((0..self.bitfield.len() * 8).filter(|position| self.is_set(*position)).collect())
+        } else {
+            None
+        };
+        SerializedFilter {
+            key_size: self.key_size,
+            bitfield: if sparse { None } else { Some(&self.bitfield) },
+            set_bits,
+            bitfield_size: self.bitfield_size,
+            terms: self.terms.as_ref(),
+            bit_order: self.bit_order
+        }.serialize(serializer)
+    }
+}
+
+impl TryFrom<DeserializedFilter> for BloomFilter {
+    type Error = Error;
+
+    fn try_from(serialized: DeserializedFilter) -> Result<Self, Self::Error> {
+        let bitfield = match (serialized.bitfield, serialized.set_bits) {
+            (Some(bitfield), None) => bitfield,
+            (None, Some(set_bits)) => {
+                let mut bitfield = vec![0; serialized.bitfield_size.div_ceil(8)];
+                for position in set_bits {
+                    let (array_index, mask) = bit_location(position, serialized.bit_order);
+                    match bitfield.get_mut(array_index) {
+                        Some(byte) => *byte |= mask,
+                        None => return Err(Error::InvalidBitfield)
+                    }
+                }
+                bitfield
+            },
+            _ => return Err(Error::InvalidBitfield)
+        };
+        let mut filter = BloomFilter::from_parts(serialized.key_size, serialized.bitfield_size, bitfield)?.with_bit_order(serialized.bit_order);
+        filter.terms = serialized.terms;
+        Ok(filter)
+    }
+}
+
+impl PartialEq for BloomFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.key_size == other.key_size
+            && self.bitfield_size == other.bitfield_size
+            && self.bitfield == other.bitfield
+            && self.terms == other.terms
+            && self.bit_order == other.bit_order
+    }
+}
+
+/// Returns the index of the byte holding the bit at `position` and the mask selecting it in this byte.
+pub(crate) fn bit_location(position: usize, bit_order: BitOrder) -> (usize, u8) {
+    // The modulus guarantees a shift below 8, so it cannot overflow
+    let bit_index = position % 8;
+    let mask = match bit_order {
+        BitOrder::Lsb => 1u8 << bit_index,
+        BitOrder::Msb => 0b1000_0000u8 >> bit_index
+    };
+    (position / 8, mask)
 }
 
 #[cfg(test)]
@@ -107,6 +915,76 @@ mod tests {
         BloomFilter::new(0, 1.0);
     }
 
+    #[test]
+    fn construct_with_bitfield_size() {
+        let filter = BloomFilter::new(100, 0.01);
+        assert_eq!(filter.bitfield_size(), BloomFilter::optimal_bitfield_size(100, 0.01));
+        let clamped = BloomFilter::with_bitfield_size(100, 80);
+        assert_eq!(80, clamped.bitfield_size());
+        assert_eq!(10, clamped.bitfield.len());
+        assert_eq!(1, clamped.key_size());
+        let same = BloomFilter::with_bitfield_size(100, filter.bitfield_size());
+        assert_eq!(filter.key_size(), same.key_size());
+    }
+
+    #[test]
+    fn construct_with_memory_budget() {
+        let (filter, error_rate) = BloomFilter::with_memory_budget(1000, 1024);
+        assert_eq!(1024, filter.bitfield.len());
+        assert_eq!(8192, filter.bitfield_size());
+        let key_size = f64::from(filter.key_size());
+        let expected_rate = (1.0 - (-key_size * 1000.0 / 8192.0).exp()).powf(key_size);
+        assert!((error_rate - expected_rate).abs() < 1e-12);
+        let reference = BloomFilter::new(1000, 0.01);
+        let (filter, error_rate) = BloomFilter::with_memory_budget(1000, reference.bitfield.len());
+        assert!(filter.bitfield.len() <= reference.bitfield.len());
+        assert!((error_rate - 0.01).abs() < 0.001, "error rate of {}", error_rate);
+        let (_, smaller_error_rate) = BloomFilter::with_memory_budget(1000, reference.bitfield.len() * 2);
+        assert!(smaller_error_rate < error_rate);
+        let (_, error_rate) = BloomFilter::with_memory_budget(0, 1);
+        assert_eq!(0.0, error_rate);
+    }
+
+    #[test]
+    fn bound_hashes_of_large_budgets() {
+        for expected_terms in [0, 10] {
+            let (mut filter, error_rate) = BloomFilter::with_memory_budget(expected_terms, 1 << 20);
+            assert_eq!(MAX_KEY_SIZE, filter.key_size());
+            assert!(error_rate < 1e-9);
+            filter.insert("word").unwrap();
+            assert!(filter.contains("word").unwrap());
+        }
+        assert_eq!(MAX_KEY_SIZE, BloomFilter::new(10, 1e-9).key_size());
+    }
+
+    #[test]
+    fn use_last_partial_byte() {
+        // Positions are taken modulo the bitfield size, so the last byte is in range whatever the size
+        let mut sizes_checked = 0;
+        for capacity in 1..40 {
+            for err_rate in &[0.3, 0.1, 0.01, 0.001] {
+                let mut filter = BloomFilter::new(capacity, *err_rate);
+                if filter.bitfield_size().is_multiple_of(8) {
+                    continue;
+                }
+                sizes_checked += 1;
+                assert_eq!(filter.bitfield_size().div_ceil(8), filter.bitfield.len());
+                for i in 0..100 {
+                    let word = format!("word{}", i);
+                    filter.insert(&word).unwrap();
+                    assert!(filter.contains(&word).unwrap());
+                }
+                // The padding bits of the last byte are never set
+                assert_eq!(0, filter.bitfield.last().unwrap() >> (filter.bitfield_size() % 8));
+            }
+        }
+        assert!(sizes_checked > 0);
+        let mut filter = BloomFilter::with_bitfield_size(10, 13);
+        (0..100).for_each(|i| filter.insert(&format!("word{}", i)).unwrap());
+        assert_eq!(13, filter.bits_set());
+        assert_eq!(2, filter.bitfield.len());
+    }
+
     #[test]
     fn insert_new_key() {
         let mut filter = BloomFilter::new(2, 0.1);
@@ -116,6 +994,379 @@ mod tests {
         assert_eq!(vec![107, 1], filter.bitfield);
     }
 
+    #[test]
+    fn construct_from_parts() {
+        let mut original = BloomFilter::new(2, 0.1);
+        original.insert("hello").unwrap();
+        let filter = BloomFilter::from_parts(original.key_size, original.bitfield_size, original.bitfield.clone()).unwrap();
+        assert_eq!(original, filter);
+        assert!(filter.contains("hello").unwrap());
+        assert!(!filter.contains("world").unwrap());
+        assert!(BloomFilter::from_parts(4, 9, vec![0, 0]).is_ok());
+        assert!(matches!(BloomFilter::from_parts(4, 9, vec![0]), Err(Error::InvalidBitfield)));
+        assert!(matches!(BloomFilter::from_parts(4, 8, vec![0, 0]), Err(Error::InvalidBitfield)));
+        assert!(matches!(BloomFilter::from_parts(4, 0, Vec::new()), Err(Error::InvalidBitfield)));
+    }
+
+    #[test]
+    fn lsb_bit_order_by_default() {
+        let mut filter = BloomFilter::new(2, 0.1);
+        assert_eq!(BitOrder::Lsb, filter.bit_order());
+        filter.insert("hello").unwrap();
+        let dump = serde_json::to_string(&filter).unwrap();
+        assert!(!dump.contains("bit_order"));
+        let legacy_dump = format!("{{\"key_size\":{},\"bitfield\":[43,0],\"bitfield_size\":{}}}", filter.key_size, filter.bitfield_size);
+        let restored: BloomFilter = serde_json::from_str(&legacy_dump).unwrap();
+        assert_eq!(BitOrder::Lsb, restored.bit_order());
+        assert!(restored.contains("hello").unwrap());
+    }
+
+    #[test]
+    fn msb_bit_order_round_trip() {
+        let mut filter = BloomFilter::new(2, 0.1).with_bit_order(BitOrder::Msb);
+        filter.insert("hello").unwrap();
+        assert_eq!(vec![43u8.reverse_bits(), 0], filter.bitfield);
+        let restored: BloomFilter = serde_json::from_str(&serde_json::to_string(&filter).unwrap()).unwrap();
+        assert_eq!(filter, restored);
+        assert_eq!(BitOrder::Msb, restored.bit_order());
+        assert!(restored.contains("hello").unwrap());
+        assert!(!restored.contains("world").unwrap());
+        let foreign = BloomFilter::from_parts(filter.key_size, filter.bitfield_size, vec![43u8.reverse_bits(), 0]).unwrap().with_bit_order(BitOrder::Msb);
+        assert!(foreign.contains("hello").unwrap());
+        let mut filter = BloomFilter::with_terms(2, 0.1).with_bit_order(BitOrder::Msb);
+        filter.insert("hello").unwrap();
+        filter.resize(10, 0.1).unwrap();
+        assert_eq!(BitOrder::Msb, filter.bit_order());
+        assert!(filter.contains("hello").unwrap());
+    }
+
+    #[test]
+    fn sparse_serialization() {
+        let mut filter = BloomFilter::with_terms(1000, 0.01).with_bit_order(BitOrder::Msb);
+        filter.insert("hello").unwrap();
+        filter.insert("world").unwrap();
+        let sparse_dump = serde_json::to_string(&filter).unwrap();
+        assert!(sparse_dump.contains("set_bits") && !sparse_dump.contains("bitfield\""));
+        let dense_dump = serde_json::to_string(&SerializedFilter {
+            key_size: filter.key_size,
+            bitfield: Some(&filter.bitfield),
+            set_bits: None,
+            bitfield_size: filter.bitfield_size,
+            terms: filter.terms.as_ref(),
+            bit_order: filter.bit_order
+        }).unwrap();
+        assert!(sparse_dump.len() < dense_dump.len());
+        for dump in &[sparse_dump, dense_dump] {
+            let restored: BloomFilter = serde_json::from_str(dump).unwrap();
+            assert_eq!(filter, restored);
+            assert!(restored.contains("hello").unwrap());
+        }
+        let mut dense_filter = BloomFilter::new(2, 0.1);
+        dense_filter.insert("hello").unwrap();
+        assert!(serde_json::to_string(&dense_filter).unwrap().contains("\"bitfield\":[43,0]"));
+    }
+
+    #[test]
+    fn reject_invalid_serialized_bitfield() {
+        for dump in &[
+            "{\"key_size\":4,\"bitfield\":[0],\"bitfield_size\":16}",
+            "{\"key_size\":4,\"set_bits\":[16],\"bitfield_size\":16}",
+            "{\"key_size\":4,\"bitfield_size\":16}",
+            "{\"key_size\":4,\"bitfield\":[0,0],\"set_bits\":[],\"bitfield_size\":16}"
+        ] {
+            assert!(serde_json::from_str::<BloomFilter>(dump).is_err(), "{} should be invalid", dump);
+        }
+    }
+
+    #[test]
+    fn estimate_false_positive_rate() {
+        let mut filter = BloomFilter::new(2, 0.1);
+        assert_eq!(0.0, filter.false_positive_rate());
+        filter.bitfield = vec![255, 255];
+        filter.bitfield_size = 16;
+        assert_eq!(1.0, filter.false_positive_rate());
+        filter.bitfield = vec![15, 15];
+        assert_eq!(0.5_f64.powi(filter.key_size as i32), filter.false_positive_rate());
+    }
+
+    #[test]
+    fn estimate_count_of_distinct_words() {
+        let mut filter = BloomFilter::new(1000, 0.001);
+        assert_eq!(0, filter.estimated_count());
+        for i in 0..1000 {
+            filter.insert(&format!("word{}", i)).unwrap();
+        }
+        let error = (filter.estimated_count() as f64 - 1000.0).abs() / 1000.0;
+        assert!(error < 0.05, "1000 words estimated with an error of {}", error);
+        filter.bitfield = vec![255, 255];
+        filter.bitfield_size = 16;
+        assert_eq!(usize::MAX, filter.estimated_count());
+    }
+
+    #[test]
+    fn retain_terms() {
+        let mut filter = BloomFilter::with_terms(2, 0.1);
+        filter.insert("hello").unwrap();
+        filter.insert("world").unwrap();
+        filter.insert("hello").unwrap();
+        assert_eq!(vec![107, 1], filter.bitfield);
+        assert_eq!(vec!["hello", "world"], filter.terms().unwrap().iter().collect::<Vec<&String>>());
+        assert!(!BloomFilter::new(2, 0.1).retains_terms());
+    }
+
+    #[test]
+    fn rebuild_with_new_geometry() {
+        let mut filter = BloomFilter::with_terms(2, 0.1);
+        filter.insert("hello").unwrap();
+        filter.insert("world").unwrap();
+        filter.resize(100, 0.01).unwrap();
+        let expected = BloomFilter::with_terms(100, 0.01);
+        assert_eq!(expected.bitfield_size, filter.bitfield_size);
+        assert_eq!(expected.key_size, filter.key_size);
+        assert!(filter.contains("hello").unwrap());
+        assert!(filter.contains("world").unwrap());
+        assert_eq!(2, filter.terms().unwrap().len());
+        let mut plain_filter = BloomFilter::new(2, 0.1);
+        assert!(matches!(plain_filter.resize(100, 0.01), Err(Error::TermsNotRetained)));
+        assert_eq!(BloomFilter::new(2, 0.1), plain_filter);
+    }
+
+    #[test]
+    fn resize_up_and_down() {
+        let words: Vec<String> = (0..50).map(|word| format!("word{}", word)).collect();
+        let mut filter = BloomFilter::with_terms(words.len(), 0.01);
+        for word in &words {
+            filter.insert(word).unwrap();
+        }
+        for (capacity, err_rate) in &[(500, 0.0001), (10, 0.1), (0, 0.5), (50, 0.01)] {
+            filter.resize(*capacity, *err_rate).unwrap();
+            let expected = BloomFilter::with_terms((*capacity).max(1), *err_rate);
+            assert_eq!((expected.key_size, expected.bitfield_size), (filter.key_size, filter.bitfield_size));
+            assert!(words.iter().all(|word| filter.contains(word).unwrap()));
+            assert_eq!(words.len(), filter.terms().unwrap().len());
+        }
+    }
+
+    #[test]
+    fn rebuild_without_some_terms() {
+        let mut filter = BloomFilter::with_terms(3, 0.01);
+        filter.insert("hello").unwrap();
+        filter.insert("the").unwrap();
+        filter.insert("world").unwrap();
+        filter.retain_terms(|term| term != "the", 0.01).unwrap();
+        assert_eq!(BloomFilter::with_terms(2, 0.01).bitfield_size, filter.bitfield_size);
+        assert!(!filter.contains("the").unwrap());
+        assert!(filter.contains("hello").unwrap());
+        assert_eq!(vec!["hello", "world"], filter.terms().unwrap().iter().collect::<Vec<&String>>());
+        let mut plain_filter = BloomFilter::new(2, 0.1);
+        assert!(matches!(plain_filter.retain_terms(|_| true, 0.01), Err(Error::TermsNotRetained)));
+    }
+
+    #[test]
+    fn warn_when_saturated() {
+        let mut filter = BloomFilter::new(5, 0.1);
+        for token in 0..5 {
+            filter.insert(&format!("word{}", token)).unwrap();
+        }
+        assert!(filter.fill_ratio() <= BloomFilter::FILL_RATIO_WARNING);
+        assert_eq!(None, filter.last_warning());
+        for token in 5..20 {
+            filter.insert(&format!("word{}", token)).unwrap();
+        }
+        let fill_ratio = filter.fill_ratio();
+        assert!(fill_ratio > BloomFilter::FILL_RATIO_WARNING);
+        assert_eq!(Some(Warning::Saturated { fill_ratio }), filter.last_warning());
+    }
+
+    #[test]
+    fn shrink_bitfield() {
+        let mut filter = BloomFilter::new(2, 0.1);
+        let expected_usage = filter.heap_usage();
+        filter.bitfield.reserve(1000);
+        assert!(filter.heap_usage() > expected_usage);
+        filter.shrink_to_fit();
+        assert_eq!(expected_usage, filter.heap_usage());
+    }
+
+    #[test]
+    fn count_probes() {
+        let mut filter = BloomFilter::new(2, 0.1);
+        filter.insert("hello").unwrap();
+        let mut probes = 0;
+        assert!(filter.probe("hello", &mut probes).unwrap());
+        assert_eq!(filter.key_size as usize, probes);
+        assert!(!filter.probe("foobar", &mut probes).unwrap());
+        assert!(probes > filter.key_size as usize);
+        assert!(probes <= 2 * filter.key_size as usize);
+    }
+
+    #[test]
+    fn self_test_known_vectors() {
+        assert!(BloomFilter::self_test());
+        assert!(BloomFilter::self_test_with(blake2b_digest));
+        let broken_digest: fn(&[u8]) -> Vec<u8> = |data| {
+            let mut digest = blake2b_digest(data);
+            digest[0] ^= 1;
+            digest
+        };
+        assert!(!BloomFilter::self_test_with(broken_digest));
+        assert!(!BloomFilter::self_test_with(|_| vec![0, 0, 0, 0]));
+        assert!(!BloomFilter::self_test_with(|_| Vec::new()));
+    }
+
+    #[test]
+    fn score_bits_distribution() {
+        let words: Vec<String> = (0..1000).map(|i| format!("word{}", i)).collect();
+        let mut filter = BloomFilter::new(words.len(), 0.01);
+        assert_eq!(1.0, filter.distribution_score());
+        for word in &words {
+            filter.insert(word).unwrap();
+        }
+        assert_eq!(1.0, filter.distribution_score());
+        let mut clustered = BloomFilter::new(words.len(), 0.01).with_digest(|data| vec![blake2b_digest(data)[0] & 0x3f]);
+        for word in &words {
+            clustered.insert(word).unwrap();
+        }
+        assert!(clustered.distribution_score() < 0.05, "score {}", clustered.distribution_score());
+    }
+
+    #[test]
+    fn bit_location_masks() {
+        for bit_index in 0..8 {
+            assert_eq!((0, (2u8).pow(bit_index as u32)), bit_location(bit_index, BitOrder::Lsb));
+            assert_eq!((3, (2u8).pow(bit_index as u32)), bit_location(24 + bit_index, BitOrder::Lsb));
+            assert_eq!((3, (2u8).pow(7 - bit_index as u32)), bit_location(24 + bit_index, BitOrder::Msb));
+        }
+        assert_eq!((0, 0b0000_0001), bit_location(0, BitOrder::Lsb));
+        assert_eq!((0, 0b1000_0000), bit_location(7, BitOrder::Lsb));
+        assert_eq!((1, 0b0000_0001), bit_location(8, BitOrder::Lsb));
+        assert_eq!((0, 0b1000_0000), bit_location(0, BitOrder::Msb));
+        assert_eq!((0, 0b0000_0001), bit_location(7, BitOrder::Msb));
+    }
+
+    #[test]
+    fn contains_prehashed_key() {
+        let mut small_filter = BloomFilter::new(2, 0.1);
+        let mut large_filter = BloomFilter::new(100, 0.0001);
+        small_filter.insert("hello").unwrap();
+        large_filter.insert("hello").unwrap();
+        let key_size = small_filter.key_size().max(large_filter.key_size());
+        let hashes = BloomFilter::word_hashes("hello", key_size).unwrap();
+        assert!(small_filter.contains_hashes(&hashes));
+        assert!(large_filter.contains_hashes(&hashes));
+        let hashes = BloomFilter::word_hashes("foobar", key_size).unwrap();
+        assert_eq!(large_filter.contains("foobar").unwrap(), large_filter.contains_hashes(&hashes));
+        assert_eq!(small_filter.contains("foobar").unwrap(), small_filter.contains_hashes(&hashes));
+    }
+
+    #[test]
+    fn check_geometry_compatibility() {
+        let mut filter = BloomFilter::new(10, 0.01);
+        let empty_copy = BloomFilter::new(10, 0.01);
+        filter.insert("hello").unwrap();
+        assert!(filter.is_compatible_with(&empty_copy));
+        assert!(empty_copy.is_compatible_with(&filter));
+        assert!(filter.is_compatible_with(&BloomFilter::with_terms(10, 0.01)));
+        assert!(!filter.is_compatible_with(&BloomFilter::new(11, 0.01)));
+        assert!(!filter.is_compatible_with(&BloomFilter::new(10, 0.001)));
+        assert!(!filter.is_compatible_with(&BloomFilter::new(10, 0.01).with_bit_order(BitOrder::Msb)));
+        let same_size = BloomFilter::from_parts(filter.key_size() + 1, filter.bitfield_size(), vec![0; filter.bitfield.len()]).unwrap();
+        assert!(!filter.is_compatible_with(&same_size));
+    }
+
+    #[test]
+    fn merge_filters() {
+        let mut filter = BloomFilter::with_terms(100, 0.001);
+        let mut other = BloomFilter::with_terms(100, 0.001);
+        for i in 0..30 {
+            filter.insert(&format!("word{}", i)).unwrap();
+            other.insert(&format!("word{}", i + 20)).unwrap();
+        }
+        let mut merged = filter.clone();
+        merged.union(&other).unwrap();
+        for i in 0..50 {
+            assert!(merged.contains(&format!("word{}", i)).unwrap());
+        }
+        assert_eq!(50, merged.terms().unwrap().len());
+        let bits: Vec<u8> = filter.bitfield.iter().zip(&other.bitfield).map(|(byte, other_byte)| byte | other_byte).collect();
+        assert_eq!(bits, merged.bitfield);
+        merged.union(&BloomFilter::new(100, 0.001)).unwrap();
+        assert!(!merged.retains_terms());
+        for incompatible in &[BloomFilter::new(200, 0.001), BloomFilter::new(100, 0.001).with_bit_order(BitOrder::Msb)] {
+            let mut unchanged = filter.clone();
+            assert!(matches!(unchanged.union(incompatible), Err(Error::IncompatibleFilter)));
+            assert_eq!(filter, unchanged);
+            assert!(unchanged.retains_terms());
+        }
+    }
+
+    #[test]
+    fn compare_approximately() {
+        let mut filter = BloomFilter::new(100, 0.01);
+        let mut same_words = BloomFilter::new(100, 0.01);
+        let mut close_words = BloomFilter::new(100, 0.01);
+        let mut other_words = BloomFilter::new(100, 0.01);
+        assert!(filter.approximately_equals(&other_words, 0.0).unwrap());
+        for word in 0..50 {
+            filter.insert(&format!("word{}", word)).unwrap();
+            same_words.insert(&format!("word{}", 49 - word)).unwrap();
+            close_words.insert(&format!("word{}", word + 1)).unwrap();
+            other_words.insert(&format!("other{}", word)).unwrap();
+        }
+        assert!(filter.approximately_equals(&same_words, 0.0).unwrap());
+        assert!(!filter.approximately_equals(&close_words, 0.0).unwrap());
+        assert!(filter.approximately_equals(&close_words, 0.1).unwrap());
+        assert!(!filter.approximately_equals(&other_words, 0.5).unwrap());
+        assert!(filter.approximately_equals(&other_words, 1.0).unwrap());
+        assert!(matches!(filter.approximately_equals(&BloomFilter::new(200, 0.01), 1.0), Err(Error::IncompatibleFilter)));
+    }
+
+    #[test]
+    fn verify_consistency() {
+        let mut filter = BloomFilter::with_terms(10, 0.01);
+        filter.insert("hello").unwrap();
+        assert!(filter.verify().is_ok());
+        assert!(BloomFilter::from_parts(4, 24, vec![255, 255, 255]).unwrap().verify().is_ok());
+        assert!(matches!(BloomFilter::from_parts(4, 20, vec![0, 0, 0x10]).unwrap().verify(), Err(Error::CorruptedFilter)));
+        let msb_filter = BloomFilter::from_parts(4, 20, vec![0, 0, 0x08]).unwrap().with_bit_order(BitOrder::Msb);
+        assert!(matches!(msb_filter.verify(), Err(Error::CorruptedFilter)));
+        assert!(matches!(BloomFilter::from_parts(0, 24, vec![0, 0, 0]).unwrap().verify(), Err(Error::CorruptedFilter)));
+        let mut missing_term = filter.clone();
+        missing_term.bitfield.iter_mut().for_each(|byte| *byte = 0);
+        assert!(matches!(missing_term.verify(), Err(Error::CorruptedFilter)));
+        let mut truncated = filter.clone();
+        truncated.bitfield.pop();
+        assert!(matches!(truncated.verify(), Err(Error::InvalidBitfield)));
+    }
+
+    #[test]
+    fn contains_with_streamed_positions() {
+        let mut filter = BloomFilter::new(100, 0.0001);
+        let words: Vec<String> = (0..100).map(|i| format!("word{}", i)).collect();
+        for word in &words {
+            filter.insert(word).unwrap();
+        }
+        for word in words.iter().chain(&["absent".to_string(), "".to_string(), "é\u{0}".to_string()]) {
+            let positions = filter.hash_word(word).unwrap();
+            assert_eq!(positions.iter().all(|position| filter.is_set(*position)), filter.contains(word).unwrap());
+            let streamed: Vec<usize> = (1..=filter.key_size).map(|repetitions| blake2b_hash(word, repetitions) % filter.bitfield_size).collect();
+            assert_eq!(positions, streamed);
+        }
+    }
+
+    #[test]
+    fn filter_contains_none_of_the_keys() {
+        let mut filter = BloomFilter::new(2, 0.0001);
+        filter.insert("hello").unwrap();
+        filter.insert("world").unwrap();
+        let terms = |words: &[&str]| words.iter().map(|word| word.to_string()).collect::<Vec<String>>();
+        assert!(filter.probably_contains_none(&terms(&["foobar", "bloom"])).unwrap());
+        assert!(filter.probably_contains_none(&[]).unwrap());
+        // A false positive on an excluded word would wrongly exclude the filter, never the opposite
+        assert!(!filter.probably_contains_none(&terms(&["foobar", "world"])).unwrap());
+        assert!(!filter.probably_contains_none(&terms(&["hello"])).unwrap());
+    }
+
     #[test]
     fn filter_contains_a_key() {
         let mut filter = BloomFilter::new(2, 0.1);
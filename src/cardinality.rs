@@ -0,0 +1,84 @@
+use std::convert::TryInto;
+use blake2::VarBlake2b;
+use blake2::digest::{Update, VariableOutput};
+
+/// The number of bits of a hash selecting a register, giving a standard error of about 0.8%.
+const PRECISION: u32 = 14;
+
+/// A HyperLogLog estimator of the number of unique words, using a fixed amount of memory whatever the number of words.
+pub(crate) struct HyperLogLog {
+    registers: Vec<u8>
+}
+
+impl HyperLogLog {
+    pub(crate) fn new() -> Self {
+        HyperLogLog {
+            registers: vec![0; 1 << PRECISION]
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: &str) {
+        let hash = blake2b_hash(key.as_bytes());
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // The rank is the position of the first set bit in the remaining bits, capped when they are all zeros
+        let rank = ((hash << PRECISION).leading_zeros().min(64 - PRECISION) + 1) as u8;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+        }
+    }
+
+    /// Returns the estimated number of unique words inserted.
+    pub(crate) fn estimate(&self) -> usize {
+        let registers_count = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / registers_count);
+        let sum: f64 = self.registers.iter().map(|rank| 2f64.powi(-i32::from(*rank))).sum();
+        let estimate = alpha * registers_count * registers_count / sum;
+        let empty_registers = self.registers.iter().filter(|rank| **rank == 0).count();
+        if estimate <= 2.5 * registers_count && empty_registers > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (registers_count * (registers_count / empty_registers as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+/// Returns the first 8 bytes of the Blake2b digest of `data`.
+fn blake2b_hash(data: &[u8]) -> u64 {
+    let mut hasher = VarBlake2b::new(8).unwrap();
+    hasher.update(data);
+    let mut hash = 0;
+    hasher.finalize_variable(|digest| {
+        hash = u64::from_be_bytes(digest.try_into().unwrap());
+    });
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_small_cardinalities() {
+        let mut estimator = HyperLogLog::new();
+        assert_eq!(0, estimator.estimate());
+        for _ in 0..3 {
+            for i in 0..100 {
+                estimator.insert(&format!("word{}", i));
+            }
+        }
+        assert_eq!(100, estimator.estimate());
+    }
+
+    #[test]
+    fn estimate_within_error_bound() {
+        for cardinality in &[10_000, 50_000, 200_000] {
+            let mut estimator = HyperLogLog::new();
+            for i in 0..*cardinality {
+                estimator.insert(&format!("word{}", i));
+            }
+            let error = (estimator.estimate() as f64 - *cardinality as f64).abs() / *cardinality as f64;
+            assert!(error < 0.03, "{} words estimated with an error of {}", cardinality, error);
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, BTreeSet};
+use blake2::VarBlake2b;
+use blake2::digest::{Update, VariableOutput};
+use serde::{Serialize, Deserialize};
+
+use crate::tokens::Tokens;
+
+/// The version of the normalization rules of the built-in tokenizer, to be bumped whenever they change.
+const TOKENIZER_VERSION: u32 = 1;
+
+/// The configuration of an [`Index`](crate::Index).
+///
+/// The configuration is stored in the dump of an `Index` so that a restored `Index` behaves exactly like the original one.
+/// New options may be added in future versions, so it is built from [`IndexConfig::new`] and the `with_*` methods.
+///
+/// # Example
+///
+/// ```
+/// # use index_bloom::{Index, IndexConfig};
+/// let config = IndexConfig::new(0.00001)
+///     .with_stop_words(vec!["a", "the"])
+///     .with_min_token_length(2);
+/// let index = Index::with_config(config);
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// The probability of false positive when searching for keywords.
+    pub error_rate: f32,
+    /// Words ignored at ingestion and search time.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub stop_words: BTreeSet<String>,
+    /// The minimum length, in characters, of an indexed word.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_token_length: Option<usize>,
+    /// The maximum length, in characters, of an indexed word.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_token_length: Option<usize>,
+    /// Whether the accented form of words is indexed too, enabling [`Index::search_strict`](crate::Index::search_strict).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preserve_accents: bool,
+    /// Alternative words matched by [`Index::search_expanded`](crate::Index::search_expanded) for each word.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub synonyms: BTreeMap<String, Vec<String>>,
+    /// The maximum number of unique words indexed per document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_unique_terms: Option<usize>,
+    /// The maximum size, in bytes, of the bitfield of a new filter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bitfield_bytes: Option<usize>,
+    /// Whether filters exceeding the maximum size of bitfield are shrunk to this size rather than refused.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clamp_bitfield: bool,
+    /// Whether the filters of new documents retain their words (see [`BloomFilter::with_terms`](crate::BloomFilter::with_terms)).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retain_terms: bool,
+    /// Whether documents names are normalized as paths, so that equivalent paths identify the same document.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub normalize_names: bool,
+    /// Whether documents ingested with the same content share a single filter.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub share_filters: bool,
+    /// The maximum size, in bytes, of the content of a document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_content_bytes: Option<usize>,
+    /// The maximum total size, in bytes, of the unique words of a query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_query_bytes: Option<usize>,
+    /// Whether zero-width characters and combining marks are removed before splitting words.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strip_invisible_characters: bool,
+    /// Whether words are split on Unicode word boundaries rather than on whitespace.
+    #[cfg(feature = "segmentation")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unicode_word_boundaries: bool,
+    /// Whether the stem of words is indexed too, enabling [`Index::search_stemmed`](crate::Index::search_stemmed).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub index_stems: bool,
+    /// The casing rules used to lowercase words.
+    #[serde(default, skip_serializing_if = "Locale::is_default")]
+    pub locale: Locale,
+    /// What happens to words without any letter nor digit once normalized, such as `"☃"`.
+    #[serde(default, skip_serializing_if = "EmptyWordPolicy::is_skip")]
+    pub empty_word_policy: EmptyWordPolicy,
+    /// What happens to a search when a keyword cannot be hashed.
+    #[serde(default, skip_serializing_if = "HashErrorPolicy::is_fail_closed")]
+    pub hash_error_policy: HashErrorPolicy,
+    /// How the scores of the fields of a document add up in [`Index::search_fields_ranked`](crate::Index::search_fields_ranked).
+    #[serde(default, skip_serializing_if = "FieldCombiner::is_max")]
+    pub field_combiner: FieldCombiner
+}
+
+/// The casing rules used to lowercase words, see [`IndexConfig::with_locale`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// The default Unicode casing rules.
+    #[default]
+    Default,
+    /// The Turkish and Azerbaijani casing rules, where `I` lowercases to `ı` and `İ` to `i`.
+    Turkish
+}
+
+impl Locale {
+    fn is_default(&self) -> bool {
+        *self == Locale::Default
+    }
+}
+
+/// What happens to words without any letter nor digit once normalized, see [`IndexConfig::with_empty_word_policy`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyWordPolicy {
+    /// The word is ignored.
+    #[default]
+    Skip,
+    /// The word is indexed as the replacement character `\u{FFFD}`, so that searching any such word matches documents containing one.
+    Placeholder
+}
+
+impl EmptyWordPolicy {
+    fn is_skip(&self) -> bool {
+        *self == EmptyWordPolicy::Skip
+    }
+}
+
+/// What happens to a search when a keyword cannot be hashed, see [`IndexConfig::with_hash_error_policy`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashErrorPolicy {
+    /// The search is aborted and returns the error.
+    #[default]
+    FailClosed,
+    /// The keyword matches no document and the search goes on.
+    FailOpen
+}
+
+impl HashErrorPolicy {
+    fn is_fail_closed(&self) -> bool {
+        *self == HashErrorPolicy::FailClosed
+    }
+}
+
+/// How the scores of the fields of a document add up, see [`IndexConfig::with_field_combiner`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldCombiner {
+    /// The score of a document is the score of its best field.
+    #[default]
+    Max,
+    /// The score of a document is the sum of the scores of its fields.
+    Sum,
+    /// The score of a document is the sum of the scores of its fields multiplied by their weight, `1.0` for the fields missing from the map.
+    WeightedSum(BTreeMap<String, f64>)
+}
+
+impl FieldCombiner {
+    fn is_max(&self) -> bool {
+        *self == FieldCombiner::Max
+    }
+
+    /// Combines the `(field, score)` pairs of a document into its score.
+    pub(crate) fn combine<'a, I: IntoIterator<Item = (&'a str, f64)>>(&self, scores: I) -> f64 {
+        let scores = scores.into_iter();
+        match self {
+            FieldCombiner::Max => scores.map(|(_, score)| score).fold(0.0, f64::max),
+            FieldCombiner::Sum => scores.map(|(_, score)| score).sum(),
+            FieldCombiner::WeightedSum(weights) => scores
+                .map(|(field, score)| score * weights.get(field).copied().unwrap_or(1.0))
+                .sum()
+        }
+    }
+}
+
+impl IndexConfig {
+    /// Constructs a new configuration with the specified `error_rate` and default options.
+    pub fn new(error_rate: f32) -> Self {
+        IndexConfig {
+            error_rate,
+            stop_words: BTreeSet::new(),
+            min_token_length: None,
+            max_token_length: None,
+            preserve_accents: false,
+            synonyms: BTreeMap::new(),
+            max_unique_terms: None,
+            max_bitfield_bytes: None,
+            clamp_bitfield: false,
+            retain_terms: false,
+            normalize_names: false,
+            share_filters: false,
+            max_content_bytes: None,
+            max_query_bytes: None,
+            strip_invisible_characters: false,
+            #[cfg(feature = "segmentation")]
+            unicode_word_boundaries: false,
+            index_stems: false,
+            locale: Locale::Default,
+            empty_word_policy: EmptyWordPolicy::Skip,
+            hash_error_policy: HashErrorPolicy::FailClosed,
+            field_combiner: FieldCombiner::Max
+        }
+    }
+
+    /// Ignore the given `stop_words` at ingestion and search time.
+    ///
+    /// Stop words are normalized the same way as the content, so `"The"` and `"the"` are equivalent.
+    pub fn with_stop_words<I, S>(mut self, stop_words: I) -> Self
+        where I: IntoIterator<Item = S>, S: AsRef<str> {
+        for word in stop_words {
+            self.stop_words.extend(Tokens::new(word.as_ref()));
+        }
+        self
+    }
+
+    /// Ignore words shorter than `length` characters.
+    pub fn with_min_token_length(mut self, length: usize) -> Self {
+        self.min_token_length = Some(length);
+        self
+    }
+
+    /// Ignore words longer than `length` characters.
+    pub fn with_max_token_length(mut self, length: usize) -> Self {
+        self.max_token_length = Some(length);
+        self
+    }
+
+    /// Index the accented form of words beside their accent-insensitive form.
+    ///
+    /// Searches remain accent-insensitive, while [`Index::search_strict`](crate::Index::search_strict) distinguishes accents.
+    /// Filters get bigger as most accented words are stored twice.
+    pub fn with_preserved_accents(mut self) -> Self {
+        self.preserve_accents = true;
+        self
+    }
+
+    /// Index the stem of words beside their raw form (see [`Stemmer`](crate::analyzer::Stemmer)).
+    ///
+    /// Searches still match raw words exactly, while [`Index::search_stemmed`](crate::Index::search_stemmed) matches any word sharing the same stem, so that `"apple"` matches `"apples"`.
+    /// Filters get bigger as most words are stored twice.
+    pub fn with_indexed_stems(mut self) -> Self {
+        self.index_stems = true;
+        self
+    }
+
+    /// Lowercase words with the casing rules of a `locale`, at ingestion and search time.
+    ///
+    /// Accent-insensitive searches match the same words whatever the locale, while the accented form of words indexed with
+    /// [`IndexConfig::with_preserved_accents`] depends on it: with [`Locale::Turkish`], `"İSTANBUL"` is matched by [`Index::search_strict`](crate::Index::search_strict) with `"istanbul"`.
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Choose what happens to words left empty by normalization, such as symbols or punctuation only words like `"☃"` or `"!!!"`.
+    ///
+    /// Such words are skipped by default. Use [`Index::ingest_with_stats`](crate::Index::ingest_with_stats) to count them.
+    pub fn with_empty_word_policy(mut self, policy: EmptyWordPolicy) -> Self {
+        self.empty_word_policy = policy;
+        self
+    }
+
+    /// Choose whether a keyword which cannot be hashed aborts the search or matches no document.
+    ///
+    /// With [`HashErrorPolicy::FailOpen`], such a keyword never matches: a search requiring all keywords matches nothing, while [`Index::search_ranked`](crate::Index::search_ranked) still ranks the documents matching the other keywords.
+    /// Errors raised while ingesting are not affected.
+    pub fn with_hash_error_policy(mut self, policy: HashErrorPolicy) -> Self {
+        self.hash_error_policy = policy;
+        self
+    }
+
+    /// Choose how the scores of the fields of a document add up in [`Index::search_fields_ranked`](crate::Index::search_fields_ranked).
+    ///
+    /// With [`FieldCombiner::Max`], a document matching a keyword in several fields scores the same as a document matching it in one field,
+    /// while [`FieldCombiner::Sum`] and [`FieldCombiner::WeightedSum`] reward matches repeated across fields. Field names are in lowercase.
+    pub fn with_field_combiner(mut self, combiner: FieldCombiner) -> Self {
+        self.field_combiner = combiner;
+        self
+    }
+
+    /// Index at most `limit` unique words per document, bounding the size of its filter.
+    ///
+    /// Only the first `limit` unique words of a document, in order of appearance, are indexed: searching for any further word will not match the document.
+    pub fn with_max_unique_terms(mut self, limit: usize) -> Self {
+        self.max_unique_terms = Some(limit);
+        self
+    }
+
+    /// Refuse to build filters whose bitfield would exceed `limit` bytes.
+    ///
+    /// The size of a filter grows with the number of unique words of its document, so this bounds the memory used by a pathological document.
+    /// Ingesting such a document returns an error, unless [`IndexConfig::with_clamped_bitfield`] is used.
+    pub fn with_max_bitfield_bytes(mut self, limit: usize) -> Self {
+        self.max_bitfield_bytes = Some(limit);
+        self
+    }
+
+    /// Shrink the filters exceeding the maximum size of [`IndexConfig::with_max_bitfield_bytes`] to this size instead of refusing them.
+    ///
+    /// A clamped filter holds more words than it was sized for, so its probability of false positive exceeds the error rate,
+    /// as reported by [`IngestStats::clamped`](crate::IngestStats::clamped).
+    pub fn with_clamped_bitfield(mut self) -> Self {
+        self.clamp_bitfield = true;
+        self
+    }
+
+    /// Remove zero-width characters and combining marks from the content and the keywords before splitting words.
+    ///
+    /// Zero-width spaces separate words like regular spaces, while other invisible characters and combining marks are dropped, so that words typed or copied with different invisible characters are indexed the same way.
+    /// Precomposed accented letters such as `é` are not affected.
+    pub fn with_stripped_invisible_characters(mut self) -> Self {
+        self.strip_invisible_characters = true;
+        self
+    }
+
+    /// Split the content and the keywords into words on the Unicode word boundaries of UAX #29, rather than on whitespace before stripping punctuation.
+    ///
+    /// Words glued by punctuation, such as `"hello,world"`, are split, and so are the ideographs of scripts written without spaces.
+    /// Punctuation inside words is kept, so `"don't"` is indexed as is. The dump of such an `Index` can only be searched by a build with the `segmentation` feature.
+    #[cfg(feature = "segmentation")]
+    pub fn with_unicode_word_boundaries(mut self) -> Self {
+        self.unicode_word_boundaries = true;
+        self
+    }
+
+    /// Retain the words of new documents beside their filters.
+    ///
+    /// Filters are bigger, and words are stored in the dump, but they can be rebuilt: for instance [`Index::append`](crate::Index::append) grows them instead of failing.
+    pub fn with_retained_terms(mut self) -> Self {
+        self.retain_terms = true;
+        self
+    }
+
+    /// Normalize documents names as paths.
+    ///
+    /// Path separators become `/`, and `.` segments as well as repeated or trailing separators are removed, so `"./docs//a.txt"` and `"docs/a.txt"` identify the same document.
+    /// Names are normalized whenever a document is ingested or looked up, and search results return normalized names.
+    pub fn with_normalized_names(mut self) -> Self {
+        self.normalize_names = true;
+        self
+    }
+
+    /// Share a single filter between the documents ingested by [`Index::ingest`](crate::Index::ingest) with the same content.
+    ///
+    /// The content of each document is hashed, and a document whose content was already ingested under another name reuses its filter instead of building a copy.
+    /// This saves memory on corpora of templated files; searches still return every name. Filters are copied again as soon as one of them is modified, and they are not shared anymore once restored from a dump.
+    pub fn with_shared_filters(mut self) -> Self {
+        self.share_filters = true;
+        self
+    }
+
+    /// Refuse to ingest documents whose content exceeds `limit` bytes.
+    ///
+    /// This bounds the size of the filter of a document when ingesting untrusted content.
+    pub fn with_max_content_bytes(mut self, limit: usize) -> Self {
+        self.max_content_bytes = Some(limit);
+        self
+    }
+
+    /// Refuse to search queries whose unique words add up to more than `limit` bytes.
+    ///
+    /// Hashing a word costs more as it gets longer, so this bounds the work of a query coming from untrusted input.
+    /// The budget applies to the normalized words, after stop words and words of invalid length are ignored.
+    pub fn with_max_query_bytes(mut self, limit: usize) -> Self {
+        self.max_query_bytes = Some(limit);
+        self
+    }
+
+    /// Register `synonyms` for a `word`, matched in its place by [`Index::search_expanded`](crate::Index::search_expanded).
+    ///
+    /// The word and its synonyms are normalized the same way as the content. Synonyms are not symmetric: register each direction if needed.
+    pub fn with_synonyms<I, S>(mut self, word: &str, synonyms: I) -> Self
+        where I: IntoIterator<Item = S>, S: AsRef<str> {
+        if let Some(token) = Tokens::new(word).next() {
+            let alternatives = self.synonyms.entry(token).or_default();
+            for synonym in synonyms {
+                for synonym_token in Tokens::new(synonym.as_ref()) {
+                    if !alternatives.contains(&synonym_token) {
+                        alternatives.push(synonym_token);
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Returns a fingerprint of the options deciding how words are normalized and which ones are indexed.
+    ///
+    /// Two configurations with the same fingerprint tokenize content the same way, so the filters built with one can be searched with the other.
+    /// The fingerprint is stored in the dump of an [`Index`](crate::Index) and checked by [`Index::restore_with_config`](crate::Index::restore_with_config).
+    pub fn tokenizer_fingerprint(&self) -> String {
+        let options = (
+            TOKENIZER_VERSION,
+            self.locale,
+            self.preserve_accents,
+            self.strip_invisible_characters,
+            self.index_stems,
+            self.empty_word_policy,
+            &self.stop_words,
+            self.min_token_length,
+            self.max_token_length
+        );
+        let serialized = serde_json::to_vec(&options).expect("Unable to serialize tokenizer options");
+        let mut hasher = VarBlake2b::new(8).unwrap();
+        hasher.update(serialized);
+        // Hashed only when enabled, so that the fingerprints of existing dumps are unchanged
+        #[cfg(feature = "segmentation")]
+        if self.unicode_word_boundaries {
+            hasher.update(b"unicode_word_boundaries");
+        }
+        let mut fingerprint = String::new();
+        hasher.finalize_variable(|digest| {
+            fingerprint = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        });
+        fingerprint
+    }
+
+    pub(crate) fn accepts(&self, token: &str) -> bool {
+        let length = token.chars().count();
+        !self.stop_words.contains(token)
+            && self.min_token_length.is_none_or(|min| length >= min)
+            && self.max_token_length.is_none_or(|max| length <= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_accepts_every_token() {
+        let config = IndexConfig::new(0.1);
+        assert!(config.accepts("a"));
+        assert!(config.accepts("the"));
+        assert!(config.accepts("anticonstitutionnellement"));
+    }
+
+    #[test]
+    fn reject_stop_words() {
+        let config = IndexConfig::new(0.1).with_stop_words(vec!["The", "a"]);
+        assert_eq!(vec!["a", "the"], config.stop_words.iter().collect::<Vec<&String>>());
+        assert!(!config.accepts("the"));
+        assert!(!config.accepts("a"));
+        assert!(config.accepts("word"));
+    }
+
+    #[test]
+    fn normalize_synonyms() {
+        let config = IndexConfig::new(0.1)
+            .with_synonyms("Car", vec!["automobile", "Véhicule"])
+            .with_synonyms("car", vec!["AUTOMOBILE", "auto"]);
+        let expected = vec!["automobile".to_string(), "vehicule".to_string(), "auto".to_string()];
+        assert_eq!(Some(&expected), config.synonyms.get("car"));
+    }
+
+    #[test]
+    fn reject_tokens_by_length() {
+        let config = IndexConfig::new(0.1).with_min_token_length(2).with_max_token_length(4);
+        assert!(!config.accepts("a"));
+        assert!(config.accepts("ab"));
+        assert!(config.accepts("éèàç"));
+        assert!(!config.accepts("abcde"));
+    }
+
+    #[test]
+    fn fingerprint_tokenizer_options() {
+        let config = IndexConfig::new(0.1).with_stop_words(vec!["the"]);
+        assert_eq!(16, config.tokenizer_fingerprint().len());
+        assert_eq!(config.tokenizer_fingerprint(), config.clone().with_synonyms("car", vec!["auto"]).with_max_unique_terms(10).tokenizer_fingerprint());
+        assert_eq!(config.tokenizer_fingerprint(), IndexConfig::new(0.01).with_stop_words(vec!["The"]).tokenizer_fingerprint());
+        assert_ne!(config.tokenizer_fingerprint(), config.clone().with_locale(Locale::Turkish).tokenizer_fingerprint());
+        assert_ne!(config.tokenizer_fingerprint(), config.clone().with_preserved_accents().tokenizer_fingerprint());
+        assert_ne!(config.tokenizer_fingerprint(), IndexConfig::new(0.1).tokenizer_fingerprint());
+    }
+}
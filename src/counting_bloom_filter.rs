@@ -0,0 +1,193 @@
+use crate::bloom_filter::{self, BitOrder, BloomFilter};
+use crate::errors::Error;
+
+/// A Bloom filter made of counters rather than bits, so that words can be removed.
+///
+/// A counting filter uses eight times the memory of a [`BloomFilter`] of the same geometry. It suits the mutable
+/// ingestion of a document, before being frozen with [`CountingBloomFilter::to_plain`] into a compact `BloomFilter` for read-only searches.
+/// The reverse is not possible: a `BloomFilter` only knows which bits are set, not how many words set them, so its counts cannot be recovered.
+///
+/// A counter reaching 255 stays there: the words setting it can no longer be removed, which avoids false negatives.
+#[derive(Debug, Clone)]
+pub struct CountingBloomFilter {
+    key_size: u32,
+    counters: Vec<u8>,
+    bitfield_size: usize
+}
+
+impl CountingBloomFilter {
+    /// Constructs a new, empty `CountingBloomFilter` sized to hold `capacity` words with a probability `err_rate` of false positive.
+    ///
+    /// The filter has the same geometry as [`BloomFilter::new`] with the same arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::CountingBloomFilter;
+    /// let filter = CountingBloomFilter::new(100, 0.001);
+    /// ```
+    pub fn new(capacity: usize, err_rate: f32) -> Self {
+        let geometry = BloomFilter::new(capacity, err_rate);
+        CountingBloomFilter {
+            key_size: geometry.key_size(),
+            counters: vec![0; geometry.bitfield_size()],
+            bitfield_size: geometry.bitfield_size()
+        }
+    }
+
+    /// Returns the number of hashes computed for each word.
+    pub fn key_size(&self) -> u32 {
+        self.key_size
+    }
+
+    /// Returns the number of counters of the filter.
+    pub fn bitfield_size(&self) -> usize {
+        self.bitfield_size
+    }
+
+    /// Insert a word in the filter.
+    ///
+    /// # Errors
+    ///
+    /// If the word cannot be hashed then an error is returned.
+    pub fn insert(&mut self, key: &str) -> Result<(), Error> {
+        for position in self.positions(key)? {
+            self.counters[position] = self.counters[position].saturating_add(1);
+        }
+        Ok(())
+    }
+
+    /// Remove a word from the filter, returning whether it may have been present.
+    ///
+    /// Only words inserted beforehand should be removed: removing a word matched by false positive removes a part of other words.
+    /// A word which is absent for sure is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// If the word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::CountingBloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn remove_word() -> Result<(), Error> {
+    /// let mut filter = CountingBloomFilter::new(100, 0.001);
+    /// filter.insert("word")?;
+    /// assert!(filter.remove("word")?);
+    /// assert!(!filter.contains("word")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remove(&mut self, key: &str) -> Result<bool, Error> {
+        let positions = self.positions(key)?;
+        if positions.iter().any(|position| self.counters[*position] == 0) {
+            return Ok(false);
+        }
+        for position in positions {
+            if self.counters[position] < u8::MAX {
+                self.counters[position] -= 1;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Check whether a word may be present in the filter.
+    ///
+    /// A negative answer is certain, a positive one may be a false positive.
+    ///
+    /// # Errors
+    ///
+    /// If the word cannot be hashed then an error is returned.
+    pub fn contains(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.positions(key)?.iter().all(|position| self.counters[*position] > 0))
+    }
+
+    /// Freeze the filter into a plain [`BloomFilter`] of the same geometry, whose bits are set where counters are not zero.
+    ///
+    /// The plain filter answers [`BloomFilter::contains`] exactly like this filter, but words can no longer be removed from it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::CountingBloomFilter;
+    /// # use index_bloom::Error;
+    /// # fn freeze_filter() -> Result<(), Error> {
+    /// let mut filter = CountingBloomFilter::new(100, 0.001);
+    /// filter.insert("word")?;
+    /// let plain_filter = filter.to_plain();
+    /// assert!(plain_filter.contains("word")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_plain(&self) -> BloomFilter {
+        let mut bitfield = vec![0; self.bitfield_size.div_ceil(8)];
+        for (position, counter) in self.counters.iter().enumerate() {
+            if *counter > 0 {
+                let (array_index, mask) = bloom_filter::bit_location(position, BitOrder::Lsb);
+                bitfield[array_index] |= mask;
+            }
+        }
+        BloomFilter::from_parts(self.key_size, self.bitfield_size, bitfield).expect("Bitfield matches the filter geometry")
+    }
+
+    fn positions(&self, key: &str) -> Result<Vec<usize>, Error> {
+        let hashes = BloomFilter::word_hashes(key, self.key_size)?;
+        Ok(hashes.into_iter().map(|hash| hash % self.bitfield_size).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freeze_preserves_membership() {
+        let mut filter = CountingBloomFilter::new(100, 0.01);
+        let mut reference = BloomFilter::new(100, 0.01);
+        for word in 0..50 {
+            filter.insert(&format!("word{}", word)).unwrap();
+            reference.insert(&format!("word{}", word)).unwrap();
+        }
+        filter.insert("removed").unwrap();
+        filter.remove("removed").unwrap();
+        let plain_filter = filter.to_plain();
+        assert_eq!(reference, plain_filter);
+        for word in 0..200 {
+            let word = format!("word{}", word);
+            assert_eq!(filter.contains(&word).unwrap(), plain_filter.contains(&word).unwrap());
+        }
+    }
+
+    #[test]
+    fn remove_words() {
+        let mut filter = CountingBloomFilter::new(100, 0.001);
+        filter.insert("word1").unwrap();
+        filter.insert("word2").unwrap();
+        filter.insert("word2").unwrap();
+        assert!(filter.remove("word1").unwrap());
+        assert!(!filter.contains("word1").unwrap());
+        assert!(!filter.remove("word1").unwrap());
+        assert!(filter.remove("word2").unwrap());
+        assert!(filter.contains("word2").unwrap());
+        assert!(filter.remove("word2").unwrap());
+        assert!(!filter.contains("word2").unwrap());
+        assert!(filter.counters.iter().all(|counter| *counter == 0));
+    }
+
+    #[test]
+    fn saturated_counters_are_kept() {
+        let mut filter = CountingBloomFilter::new(10, 0.1);
+        for _ in 0..300 {
+            filter.insert("word").unwrap();
+        }
+        for _ in 0..300 {
+            filter.remove("word").unwrap();
+        }
+        assert!(filter.contains("word").unwrap());
+    }
+}
@@ -1,21 +1,105 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::io;
 use std::num::ParseIntError;
+use std::str::Utf8Error;
 
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum Error {
     HashWord(ParseIntError),
+    Io(io::Error),
+    Utf8(Utf8Error),
+    TermsNotRetained,
+    CapacityExceeded,
+    ContentTooLarge { limit: usize },
+    QueryBudgetExceeded { limit: usize },
+    InvalidBitfield,
+    InvalidQuery,
+    InvalidErrorRate,
+    FilterTooLarge { limit: usize },
+    Deserialize(serde_json::Error),
+    Serialize(serde_json::Error),
+    CorruptedFilter,
+    IncompatibleConfig,
+    TruncatedDump,
+    IncompatibleFilter,
 }
 
 impl StdError for Error {
 }
 
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Deserialize(error)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(error: Utf8Error) -> Self {
+        Error::Utf8(error)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
           Error::HashWord(error) => write!(f, "Error while hashing word : {}", error),
+          Error::Io(error) => write!(f, "Error while reading or writing data : {}", error),
+          Error::Utf8(error) => write!(f, "Invalid UTF-8 text : {}", error),
+          Error::TermsNotRetained => write!(f, "The Bloom filter does not retain its words and cannot be rebuilt"),
+          Error::CapacityExceeded => write!(f, "The Bloom filter capacity is exceeded and it cannot grow without its words"),
+          Error::ContentTooLarge { limit } => write!(f, "The content exceeds the maximum size of {} bytes", limit),
+          Error::QueryBudgetExceeded { limit } => write!(f, "The query exceeds the hashing budget of {} bytes", limit),
+          Error::InvalidBitfield => write!(f, "The bitfield does not match the size of the Bloom filter"),
+          Error::InvalidQuery => write!(f, "The query is not a valid boolean expression"),
+          Error::InvalidErrorRate => write!(f, "The error rate must be strictly between 0 and 1"),
+          Error::FilterTooLarge { limit } => write!(f, "The Bloom filter would exceed the maximum size of {} bytes", limit),
+          Error::Deserialize(error) => write!(f, "Invalid JSON content : {}", error),
+          Error::Serialize(error) => write!(f, "Unable to serialize to JSON : {}", error),
+          Error::CorruptedFilter => write!(f, "The Bloom filter is corrupted"),
+          Error::IncompatibleConfig => write!(f, "The dump was built with a different tokenizer configuration"),
+          Error::TruncatedDump => write!(f, "The dump is truncated, it was probably not completely written"),
+          Error::IncompatibleFilter => write!(f, "The Bloom filters do not have the same geometry"),
+        }
+    }
+}
+
+
+/// A non-fatal issue detected while using a [`BloomFilter`](crate::BloomFilter).
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warning {
+    /// The filter is so full that its probability of false positive is degraded.
+    Saturated { fill_ratio: f64 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+          Warning::Saturated { fill_ratio } => write!(f, "Bloom filter saturated : {:.0}% of bits set", fill_ratio * 100.0),
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_io_and_deserialize_errors() {
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!("Error while reading or writing data : no such file", error.to_string());
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let message = json_error.to_string();
+        let error = Error::from(json_error);
+        assert!(matches!(error, Error::Deserialize(_)));
+        assert_eq!(format!("Invalid JSON content : {}", message), error.to_string());
+    }
+}
@@ -1,15 +1,235 @@
-use std::collections::{HashMap, HashSet};
-use serde::{Serialize, Deserialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{hash_map, BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::time::{Duration, Instant};
+#[cfg(feature = "parallel")]
+use std::thread;
+use blake2::VarBlake2b;
+use blake2::digest::{Update, VariableOutput};
+#[cfg(feature = "gzip")]
+use flate2::read::GzDecoder;
+use serde::{Serialize, Serializer, Deserialize};
+use serde::de::DeserializeOwned;
+use unidecode::unidecode;
 
+use crate::analyzer::{Analyzer, Stemmer};
 use crate::bloom_filter::BloomFilter;
-use crate::tokens::Tokens;
+use crate::cardinality::HyperLogLog;
+use crate::config::{HashErrorPolicy, IndexConfig};
+use crate::query::Query;
+use crate::random::SplitMix64;
+use crate::tokens::{self, Tokens};
 use crate::errors::Error;
 
+const ACCENT_NAMESPACE: &str = "accent";
+const STEM_NAMESPACE: &str = "stem";
+/// The characters tried by [`Index::suggest`] to correct a word.
+const SUGGESTION_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+
 /// An full-text search index.
+///
+/// Documents are identified by names of type `K`, which is `String` by default (see [`DocumentKey`]).
+/// Documents are serialized in order of name, so that dumps of the same `Index` are identical.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Index<K: DocumentKey = String> {
+    #[serde(flatten)]
+    config: IndexConfig,
+    #[serde(serialize_with = "serialize_sorted")]
+    bloom_filters: HashMap<K, Arc<BloomFilter>>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    insertion_order: HashMap<K, u64>,
+    #[serde(default)]
+    next_insertion: u64,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "serialize_sorted_fields")]
+    fields: HashMap<K, HashMap<String, BloomFilter>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "serialize_sorted")]
+    tags: HashMap<K, BTreeSet<String>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "serialize_sorted")]
+    aliases: HashMap<K, K>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tokenizer_fingerprint: Option<String>,
+    #[serde(skip)]
+    document_frequencies: Mutex<HashMap<String, usize>>,
+    #[serde(skip)]
+    shared_filters: HashMap<String, Weak<BloomFilter>>,
+    #[serde(skip)]
+    query_cache: Mutex<Option<QueryCache<K>>>,
+    #[serde(skip)]
+    analyzer: Option<Analyzer>,
+    #[serde(skip)]
+    auto_dump_path: Option<PathBuf>
+}
+
+/// The results of recent searches, by normalized words of the query, see [`Index::set_query_cache`].
+struct QueryCache<K> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<Vec<String>, (Instant, Option<Vec<K>>)>
+}
+
+/// The type of the names identifying the documents of an [`Index`].
+///
+/// Names are stored in the dump of an `Index`, so they must be serializable. Besides `String`, the default, integer types
+/// can be used to identify documents by numeric identifiers without converting them to strings.
+pub trait DocumentKey: Clone + Eq + Hash + Ord + Send + Sync + Serialize + DeserializeOwned {
+    /// Returns the name normalized as a path, see [`IndexConfig::with_normalized_names`]. Names are kept as is by default.
+    fn normalized(self) -> Self {
+        self
+    }
+
+    /// Returns the number of bytes allocated by the name on the heap, accounted by [`Index::memory_usage`].
+    fn heap_usage(&self) -> usize {
+        0
+    }
+
+    /// Reclaim the memory allocated by the name and left unused, see [`Index::compact`].
+    fn shrink_to_fit(&mut self) {
+    }
+}
+
+impl DocumentKey for String {
+    fn normalized(self) -> Self {
+        normalize_name(&self)
+    }
+
+    fn heap_usage(&self) -> usize {
+        self.capacity()
+    }
+
+    fn shrink_to_fit(&mut self) {
+        String::shrink_to_fit(self);
+    }
+}
+
+macro_rules! impl_integer_document_key {
+    ($($integer:ty),*) => {
+        $(impl DocumentKey for $integer {})*
+    };
+}
+
+impl_integer_document_key!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// The order applied between documents matching the same number of keywords in [`Index::search_ranked_by`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SecondarySort {
+    /// Documents are sorted by name in ascending order.
+    #[default]
+    Name,
+    /// Documents with the lowest estimated false positive rate come first, as their matches are more trustworthy.
+    FalsePositiveRate,
+    /// Documents are sorted by the order in which they were last ingested.
+    InsertionOrder
+}
+
+/// The cost of a query, as reported by [`Index::search_with_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of document filters examined.
+    pub filters_scanned: usize,
+    /// The number of bits tested across all filters.
+    pub probes: usize,
+    /// The number of matching documents.
+    pub matches: usize
+}
+
+/// The decisions taken for every document by a search, as reported by [`Index::search_trace`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchTrace<'a, K = String> {
+    /// The normalized words of the query, as tested in each filter.
+    pub terms: Vec<String>,
+    /// The decisions taken for each document, sorted by name.
+    pub documents: Vec<DocumentTrace<'a, K>>
+}
+
+/// The decision taken for a document by a search, as part of a [`SearchTrace`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentTrace<'a, K = String> {
+    /// The name of the document.
+    pub name: &'a K,
+    /// Whether each word of the query may be present in the filter of the document, in the order of [`SearchTrace::terms`].
+    pub terms: Vec<(String, bool)>,
+    /// Whether the document is returned by the search.
+    pub included: bool
+}
+
+/// The outcome of an ingestion, as reported by [`Index::ingest_with_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestStats {
+    /// The number of words left empty by normalization, such as `"☃"` or `"!!!"`, skipped or replaced by a placeholder according to [`IndexConfig::empty_word_policy`].
+    pub empty_words: usize,
+    /// Whether the filter of the document was clamped to the maximum size of [`IndexConfig::with_max_bitfield_bytes`] (see [`IndexConfig::with_clamped_bitfield`]),
+    /// its probability of false positive then exceeding the error rate.
+    pub clamped: bool
+}
+
+/// The outcome of a removal of a term, as reported by [`Index::global_remove_term`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RemovalStats {
+    /// The number of documents whose filters were rebuilt without the term.
+    pub removed: usize,
+    /// The number of documents which may still contain the term, as their filters do not retain their words.
+    pub skipped: usize
+}
+
+/// A document of a line-delimited dump, as written by [`Index::dump_lines`].
 #[derive(Serialize, Deserialize)]
-pub struct Index {
-    error_rate: f32,
-    bloom_filters: HashMap<String, BloomFilter>
+struct DumpRecord<K, F> {
+    name: K,
+    filter: F
+}
+
+/// Lightweight statistics about the filter of a document, as yielded by [`Index::iter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DocumentStats {
+    /// The number of hashes computed for each word.
+    pub key_size: u32,
+    /// The number of bits of the filter.
+    pub bitfield_size: usize,
+    /// The ratio of bits set in the filter, between 0 and 1.
+    pub fill_ratio: f64,
+    /// The estimated probability of false positive given the current fill ratio.
+    pub false_positive_rate: f64,
+    /// The estimated number of unique words inserted in the filter, see [`BloomFilter::estimated_count`].
+    pub estimated_count: usize
+}
+
+impl DocumentStats {
+    fn new(filter: &BloomFilter) -> Self {
+        DocumentStats {
+            key_size: filter.key_size(),
+            bitfield_size: filter.bitfield_size(),
+            fill_ratio: filter.fill_ratio(),
+            false_positive_rate: filter.false_positive_rate(),
+            estimated_count: filter.estimated_count()
+        }
+    }
+}
+
+/// An iterator over the documents of an [`Index`] and their statistics.
+///
+/// Created by [`Index::iter`]. Documents are yielded in arbitrary order.
+pub struct Iter<'a, K = String> {
+    filters: hash_map::Iter<'a, K, Arc<BloomFilter>>
+}
+
+impl<'a, K> Iterator for Iter<'a, K> {
+    type Item = (&'a K, DocumentStats);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.filters.next().map(|(name, filter)| (name, DocumentStats::new(filter)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.filters.size_hint()
+    }
 }
 
 impl Index {
@@ -24,15 +244,25 @@ impl Index {
     /// let mut index = Index::new(0.00001);
     /// ```
     pub fn new(error_rate: f32) -> Self {
-        Index {
-            error_rate,
-            bloom_filters: HashMap::new()
-        }
+        Index::with_config(IndexConfig::new(error_rate))
+    }
+
+    /// Constructs a new, empty `Index` with the specified configuration.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// let config = IndexConfig::new(0.00001).with_stop_words(vec!["a", "the"]);
+    /// let mut index = Index::with_config(config);
+    /// ```
+    pub fn with_config(config: IndexConfig) -> Self {
+        Index::keyed(config)
     }
 
     /// Restore an `Index` from a previous dump.
     ///
-    /// A dump is an `Index` serialized in JSON format.
+    /// A dump is an `Index` serialized in JSON format, including its configuration.
     ///
     /// # Panics
     ///
@@ -46,14 +276,84 @@ impl Index {
     /// let index = Index::restore(&index_dump);
     /// ```
     pub fn restore(content: &str) -> Self {
-        let deserialized: Index = serde_json::from_str(&content).expect("Unable to parse dump file");
-        return deserialized;
+        serde_json::from_str(content).expect("Unable to parse dump file")
     }
 
-    /// Ingest a new document.
+    /// Restore an `Index` from a previous dump, like [`Index::restore`], returning an error rather than panicking on invalid content.
     ///
-    /// Insert each word of `content` in the index and identifies them under the given `name`.
-    /// To ingest the same key twice will replace its content in the `Index`.
+    /// # Errors
+    ///
+    /// If the content ends before the dump is complete, for instance when the process writing it crashed, then [`Error::TruncatedDump`] is returned.
+    /// If the content is not a valid `Index` representation for another reason then [`Error::Deserialize`] is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Error, Index};
+    /// let index_dump = "{\"error_rate\":0.1,\"bloom_filters\":{\"file1.txt\":{\"key_size\":4,\"bitfield\":[8,130,65,18,131,164],\"bitfield_size\":48}}}";
+    /// assert!(Index::try_restore(&index_dump).is_ok());
+    /// assert!(matches!(Index::try_restore(&index_dump[..40]), Err(Error::TruncatedDump)));
+    /// ```
+    pub fn try_restore(content: &str) -> Result<Self, Error> {
+        serde_json::from_str(content).map_err(|error| {
+            if error.is_eof() {
+                Error::TruncatedDump
+            } else {
+                Error::Deserialize(error)
+            }
+        })
+    }
+
+    /// Restore an `Index` from a previous dump, replacing its configuration by `config`.
+    ///
+    /// The filters of the dump can only be searched with the normalization rules they were built with, so the tokenizer
+    /// fingerprint of the dump (see [`IndexConfig::tokenizer_fingerprint`]) must match the one of `config`.
+    /// Dumps without fingerprint are checked against the configuration they contain.
+    ///
+    /// # Errors
+    ///
+    /// If the content is not a valid `Index` representation, or if it was built with a different tokenizer configuration, then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn restore_index() -> Result<(), Error> {
+    /// let index_dump = "{\"error_rate\":0.1,\"bloom_filters\":{\"file1.txt\":{\"key_size\":4,\"bitfield\":[8,130,65,18,131,164],\"bitfield_size\":48}}}";
+    /// let index = Index::restore_with_config(&index_dump, IndexConfig::new(0.01))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn restore_with_config(content: &str, config: IndexConfig) -> Result<Self, Error> {
+        Index::restore_replacing_config(content, config, true)
+    }
+
+    /// Same as [`Index::restore_with_config`], except that a dump built with a different tokenizer configuration is accepted.
+    ///
+    /// Searches may then miss documents whose words were normalized differently, which [`Index::tokenizer_fingerprint_matches`] reports.
+    /// The dump keeps its fingerprint, so that restoring it again is still checked.
+    ///
+    /// # Errors
+    ///
+    /// If the content is not a valid `Index` representation then an error is returned.
+    pub fn restore_with_config_unchecked(content: &str, config: IndexConfig) -> Result<Self, Error> {
+        Index::restore_replacing_config(content, config, false)
+    }
+
+    /// Constructs an `Index` from an error rate and the filters of its documents, by name, as returned by [`Index::into_parts`].
+    ///
+    /// The other options of the configuration take their default value.
+    pub fn from_parts(error_rate: f32, filters: HashMap<String, BloomFilter>) -> Self {
+        let mut index = Index::new(error_rate);
+        index.bloom_filters = filters.into_iter().map(|(name, filter)| (name, Arc::new(filter))).collect();
+        index
+    }
+
+    /// Search keywords in every documents, keeping only the documents accepted by a predicate.
+    ///
+    /// Works like [`Index::search`], except that `keep` is called with the name of each matching document and only the documents for which it returns `true` are returned.
+    /// This is a hook to narrow results with metadata stored outside of the `Index`, such as permissions or dates.
     ///
     /// # Errors
     ///
@@ -66,26 +366,20 @@ impl Index {
     /// # use index_bloom::Error;
     /// # fn search_index() -> Result<(), Error> {
     /// let mut index = Index::new(0.00001);
-    /// let first_content = "A very very long content...";
-    /// index.ingest("foo".to_string(), first_content)?;
+    /// index.ingest("public/foo".to_string(), "A very very long content...")?;
+    /// index.ingest("private/bar".to_string(), "Another content !")?;
+    /// assert_eq!(vec!["public/foo"], index.search_filtered("content", |name| name.starts_with("public/"))?.unwrap());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn ingest(&mut self, name: String, content: &str) -> Result<(), Error> {
-        let tokens_agg = self.aggregate_tokens(content);
-        let capacity = tokens_agg.len();
-        let mut filter = BloomFilter::new(capacity, self.error_rate);
-        for token in tokens_agg {
-            filter.insert(&token)?;
-        }
-        self.bloom_filters.insert(name, filter);
-        Ok(())
+    pub fn search_filtered<F: Fn(&str) -> bool>(&self, keywords: &str, keep: F) -> Result<Option<Vec<&String>>, Error> {
+        self.filter_search(keywords, |name| keep(name))
     }
 
-    /// Search keywords in every documents.
+    /// Search keywords in every documents, returning at most `max_per_prefix` documents per name prefix.
     ///
-    /// Splits `keywords` and searches for each word in all documents with a boolean AND.
-    /// The result may contain false positives (documents not containing all the keywords) according to an error rate set at the creation of the `Index` (see [`Index::new`]).
+    /// Works like [`Index::search`], except that documents sharing the same first `depth` segments of their name, separated by `/`, form a group of which only the first `max_per_prefix` documents by name are returned.
+    /// This keeps a source with many matching documents, such as `logs/`, from drowning out the others.
     ///
     /// # Errors
     ///
@@ -97,112 +391,4789 @@ impl Index {
     /// # use index_bloom::Index;
     /// # use index_bloom::Error;
     /// # fn search_index() -> Result<(), Error> {
-    /// # let mut index = Index::new(0.00001);
-    /// let hits = index.search("content")?;
-    /// match hits {
-    ///      Some(documents) => {
-    ///          for doc in documents {
-    ///             println!("Found at {}", doc);
-    ///          }
-    ///      },
-    ///      None => println!("Not found")
-    /// }
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("logs/1.txt".to_string(), "error")?;
+    /// index.ingest("logs/2.txt".to_string(), "error")?;
+    /// index.ingest("docs/errors.txt".to_string(), "error")?;
+    /// assert_eq!(vec!["docs/errors.txt", "logs/1.txt"], index.search_diverse("error", 1, 1)?.unwrap());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn search(&self, keywords: &str) -> Result<Option<Vec<&String>>, Error> {
-        let mut result :Vec<&String> = Vec::new();
-        for (name, filter) in &self.bloom_filters {
-            let tokens = Tokens::new(keywords);
-            let mut all_tokens_match = true;
-            let mut in_loop = false;
-            for token in tokens {
-                in_loop = true;
-                if !filter.contains(&token)? {
-                    all_tokens_match = false;
-                    break;
-                }
-            }
-            if in_loop && all_tokens_match {
-                result.push(name);
-            }
-        }
-        return if result.len() > 0 {
-            result.sort();
+    pub fn search_diverse(&self, keywords: &str, max_per_prefix: usize, depth: usize) -> Result<Option<Vec<&String>>, Error> {
+        let documents = match self.search(keywords)? {
+            Some(documents) => documents,
+            None => return Ok(None)
+        };
+        let mut prefix_counts: HashMap<&str, usize> = HashMap::new();
+        let result: Vec<&String> = documents.into_iter()
+            .filter(|name| {
+                let count = prefix_counts.entry(name_prefix(name, depth)).or_insert(0);
+                *count += 1;
+                *count <= max_per_prefix
+            })
+            .collect();
+        if !result.is_empty() {
             Ok(Some(result))
         } else {
             Ok(None)
         }
     }
 
-    fn aggregate_tokens(&self, content: &str) -> HashSet<String> {
-        let mut unique_tokens = HashSet::new();
-        for line in content.lines() {
-            let tokens = Tokens::new(line);
-            for token in tokens {
-                unique_tokens.insert(token);
+    /// Find a document by its name, ignoring case.
+    ///
+    /// Returns the stored name of the document matching `name` case-insensitively, or `None` if there is no such document.
+    /// If several documents match, the first one in alphabetical order is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("Foo.TXT".to_string(), "A very very long content...")?;
+    /// assert_eq!(Some(&"Foo.TXT".to_string()), index.find_document_ci("foo.txt"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn find_document_ci(&self, name: &str) -> Option<&String> {
+        let lowercase_name = self.document_name(name.to_string()).to_lowercase();
+        self.bloom_filters.keys()
+            .filter(|stored_name| stored_name.to_lowercase() == lowercase_name)
+            .min()
+    }
+
+    /// Returns the names of the documents starting with `prefix`, sorted, at most `limit` of them.
+    ///
+    /// Only names are compared, not contents, so completions are exact. The comparison is case-sensitive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("docs/a.txt".to_string(), "A very very long content...")?;
+    /// index.ingest("src/b.txt".to_string(), "Another content !")?;
+    /// assert_eq!(vec!["docs/a.txt"], index.complete_name("doc", 10));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn complete_name(&self, prefix: &str, limit: usize) -> Vec<&String> {
+        let completions: BTreeSet<&String> = self.bloom_filters.keys()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        completions.into_iter().take(limit).collect()
+    }
+
+    /// Search keywords in a line-delimited dump, as written by [`Index::dump_lines`], without restoring it.
+    ///
+    /// The documents are read and tested one at a time, so only one filter is held in memory whatever the size of the dump.
+    /// The names of the documents matching every keyword are returned in order of name, like with [`Index::search`] on the restored `Index`.
+    /// Only the filters are searched: the aliases, fields and tags of the documents are not part of a line-delimited dump.
+    ///
+    /// # Errors
+    ///
+    /// If the dump cannot be read or is invalid, or if a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::fs::File;
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let hits = Index::search_streaming_dump(File::open("./index.jsonl")?, "very long content")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_streaming_dump<R: Read>(reader: R, keywords: &str) -> Result<Vec<String>, Error> {
+        let mut lines = BufReader::new(reader).lines();
+        let header = lines.next().ok_or(Error::TruncatedDump)??;
+        let searcher = Index::with_config(serde_json::from_str(&header)?);
+        let tokens = searcher.query_tokens(keywords)?;
+        let mut result = Vec::new();
+        if tokens.is_empty() {
+            return Ok(result);
+        }
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: DumpRecord<String, BloomFilter> = serde_json::from_str(&line)?;
+            let mut all_tokens_match = true;
+            for token in &tokens {
+                if !searcher.contains_token(&record.filter, token)? {
+                    all_tokens_match = false;
+                    break;
+                }
+            }
+            if all_tokens_match {
+                result.push(record.name);
             }
         }
-        unique_tokens
+        Ok(result)
     }
 }
 
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-
-    #[test]
-    fn simple_content() {
-        let mut index = Index::new(0.01);
-        let content = "word1 word2\nword3\n\nword4";
-        index.ingest("simple_content.txt".to_string(), content).expect("Unable to ingest data");
-        assert!(index.search("word1").is_ok());
-        assert_eq!(vec!["simple_content.txt"], index.search("word1").unwrap().unwrap());
-        assert_eq!(vec!["simple_content.txt"], index.search("word2").unwrap().unwrap());
-        assert_eq!(vec!["simple_content.txt"], index.search("word3").unwrap().unwrap());
-        assert_eq!(vec!["simple_content.txt"], index.search("word4").unwrap().unwrap());
-        assert_eq!(None, index.search("").unwrap());
+impl<K: DocumentKey> Index<K> {
+    /// Constructs a new, empty `Index` whose documents are named by values of type `K`, with the specified configuration.
+    ///
+    /// [`Index::new`] and [`Index::with_config`] construct an `Index` whose documents are named by `String`.
+    /// A dump of such an `Index` is restored with `serde_json` directly, as in `serde_json::from_str::<Index<u64>>(&dump)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::<u64>::keyed(IndexConfig::new(0.00001));
+    /// index.ingest(42, "A very very long content...")?;
+    /// assert_eq!(vec![&42], index.search("content")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keyed(config: IndexConfig) -> Self {
+        Index {
+            tokenizer_fingerprint: Some(config.tokenizer_fingerprint()),
+            config,
+            bloom_filters: HashMap::new(),
+            insertion_order: HashMap::new(),
+            next_insertion: 0,
+            fields: HashMap::new(),
+            tags: HashMap::new(),
+            aliases: HashMap::new(),
+            document_frequencies: Mutex::new(HashMap::new()),
+            shared_filters: HashMap::new(),
+            query_cache: Mutex::new(None),
+            analyzer: None,
+            auto_dump_path: None
+        }
     }
 
-    #[test]
-    fn several_matches() {
-        let mut index = Index::new(0.01);
-        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
-        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
-        assert_eq!(vec!["file1.txt"], index.search("word2").unwrap().unwrap());
-        let expected = vec!["file1.txt", "file2.txt"];
-        assert_eq!(expected, index.search("word1").unwrap().unwrap());
-        assert_eq!(expected, index.search("word3").unwrap().unwrap());
+    /// Returns an iterator over the documents names and their statistics, in arbitrary order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// for (name, stats) in &index {
+    ///     println!("{} has {:.0}% of bits set", name, stats.fill_ratio * 100.0);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K> {
+        Iter {
+            filters: self.bloom_filters.iter()
+        }
     }
 
-    #[test]
-    fn ingesting_twice_replace() {
-        let mut index = Index::new(0.01);
-        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
-        assert_eq!(vec!["file1.txt"], index.search("word1").unwrap().unwrap());
-        assert_eq!(None, index.search("word2").unwrap());
-        index.ingest("file1.txt".to_string(), "word2").expect("Unable to ingest data");
-        assert_eq!(None, index.search("word1").unwrap());
-        assert_eq!(vec!["file1.txt"], index.search("word2").unwrap().unwrap());
+    /// Returns the number of documents in the `Index`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn count_documents() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// assert_eq!(1, index.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn len(&self) -> usize {
+        self.bloom_filters.len()
     }
 
-    #[test]
-    fn multi_keywords_search() {
-        let mut index = Index::new(0.01);
-        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
-        assert_eq!(vec!["file1.txt"], index.search("word1 word2").unwrap().unwrap());
+    /// Returns whether the `Index` has no document.
+    pub fn is_empty(&self) -> bool {
+        self.bloom_filters.is_empty()
     }
 
-    #[test]
-    fn clean_keywords_before_search() {
-        let mut index = Index::new(0.01);
-        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
+    /// Returns whether a document named `name` is indexed.
+    ///
+    /// The name is normalized like when ingesting (see [`IndexConfig::with_normalized_names`]), while aliases are not resolved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn find_document() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// assert!(index.contains_document("foo"));
+    /// assert!(!index.contains_document("bar"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn contains_document<Q: ToOwned<Owned = K> + ?Sized>(&self, name: &Q) -> bool {
+        let name = self.document_name(name.to_owned());
+        self.bloom_filters.contains_key(&name)
+    }
+
+    /// Returns an iterator over the names of the documents, in arbitrary order.
+    ///
+    /// The order may change whenever a document is ingested or removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn list_documents() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// println!("{} documents indexed", index.documents().count());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn documents(&self) -> impl Iterator<Item = &K> + '_ {
+        self.bloom_filters.keys()
+    }
+
+    /// Returns an estimate of the memory used by the `Index`, in bytes.
+    ///
+    /// The estimate accounts for the documents names and filters, including the capacity reserved by the underlying collections.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// let index = Index::new(0.00001);
+    /// println!("The index uses about {} bytes", index.memory_usage());
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        let names_usage: usize = self.bloom_filters.keys()
+            .chain(self.insertion_order.keys())
+            .map(|name| name.heap_usage())
+            .sum();
+        // A filter shared by identical documents is only counted once
+        let filters: HashMap<*const BloomFilter, &BloomFilter> = self.bloom_filters.values()
+            .map(|filter| (Arc::as_ptr(filter), filter.as_ref()))
+            .collect();
+        let filters_usage: usize = filters.values().map(|filter| mem::size_of::<BloomFilter>() + filter.heap_usage()).sum();
+        let fields_usage: usize = self.fields.iter()
+            .map(|(name, fields)| {
+                name.heap_usage()
+                    + fields.capacity() * mem::size_of::<(String, BloomFilter)>()
+                    + fields.iter().map(|(field, filter)| field.capacity() + filter.heap_usage()).sum::<usize>()
+            })
+            .sum();
+        let tags_usage: usize = self.tags.iter()
+            .map(|(name, tags)| name.heap_usage() + tags.iter().map(|tag| mem::size_of::<String>() + tag.capacity()).sum::<usize>())
+            .sum();
+        mem::size_of::<Self>()
+            + self.bloom_filters.capacity() * mem::size_of::<(K, Arc<BloomFilter>)>()
+            + self.insertion_order.capacity() * mem::size_of::<(K, u64)>()
+            + self.fields.capacity() * mem::size_of::<(K, HashMap<String, BloomFilter>)>()
+            + self.tags.capacity() * mem::size_of::<(K, BTreeSet<String>)>()
+            + names_usage
+            + filters_usage
+            + fields_usage
+            + tags_usage
+    }
+
+    /// Reclaim the memory left unused after documents were replaced or removed.
+    ///
+    /// Shrinks every collection of the `Index` to its minimal capacity. Search results are not affected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// let mut index = Index::new(0.00001);
+    /// index.compact();
+    /// ```
+    pub fn compact(&mut self) {
+        self.bloom_filters = self.bloom_filters.drain()
+            .map(|(mut name, mut filter)| {
+                name.shrink_to_fit();
+                Arc::make_mut(&mut filter).shrink_to_fit();
+                (name, filter)
+            })
+            .collect();
+        self.bloom_filters.shrink_to_fit();
+        self.shared_filters.clear();
+        self.insertion_order = self.insertion_order.drain()
+            .map(|(mut name, order)| {
+                name.shrink_to_fit();
+                (name, order)
+            })
+            .collect();
+        self.insertion_order.shrink_to_fit();
+        self.fields = self.fields.drain()
+            .map(|(mut name, fields)| {
+                name.shrink_to_fit();
+                let fields = fields.into_iter()
+                    .map(|(mut field, mut filter)| {
+                        field.shrink_to_fit();
+                        filter.shrink_to_fit();
+                        (field, filter)
+                    })
+                    .collect();
+                (name, fields)
+            })
+            .collect();
+        self.fields.shrink_to_fit();
+        self.tags = self.tags.drain()
+            .map(|(mut name, tags)| {
+                name.shrink_to_fit();
+                (name, tags)
+            })
+            .collect();
+        self.tags.shrink_to_fit();
+    }
+
+    /// Tokenize content and keywords with a custom `analyzer` instead of the built-in tokenizer.
+    ///
+    /// The same analyzer is used at ingestion and search time, so it should be set before ingesting documents.
+    /// The options of the [`IndexConfig`] still apply to the tokens produced by the analyzer.
+    /// The analyzer is not part of the dump: it has to be set again after [`Index::restore`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::analyzer::{Analyzer, Lowercase, Stemmer};
+    /// let mut index = Index::new(0.00001);
+    /// index.set_analyzer(Analyzer::new().with_filter(Lowercase).with_filter(Stemmer));
+    /// ```
+    pub fn set_analyzer(&mut self, analyzer: Analyzer) {
+        self.analyzer = Some(analyzer);
+        self.shared_filters.clear();
+    }
+
+    /// Cache the results of up to `capacity` queries of [`Index::search`] for `ttl`.
+    ///
+    /// Queries are identified by their normalized words, so `"Word"` and `"word"` share the same results. A cached result is reused until it
+    /// is `ttl` old, and the whole cache is cleared whenever documents are ingested, changed or removed. When the cache is full, the oldest result is evicted.
+    /// Methods built on [`Index::search`], such as [`Index::search_tagged`], use the cache too. A `capacity` of 0 disables the cache, which is the default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use index_bloom::Index;
+    /// let mut index = Index::new(0.00001);
+    /// index.set_query_cache(100, Duration::from_secs(60));
+    /// ```
+    pub fn set_query_cache(&mut self, capacity: usize, ttl: Duration) {
+        let cache = self.query_cache.get_mut().unwrap_or_else(PoisonError::into_inner);
+        *cache = if capacity > 0 {
+            Some(QueryCache { capacity, ttl, entries: HashMap::new() })
+        } else {
+            None
+        };
+    }
+
+    /// Returns the configuration of the `Index`.
+    pub fn config(&self) -> &IndexConfig {
+        &self.config
+    }
+
+    /// Returns the probability of false positive of the documents ingested from now on.
+    ///
+    /// This is the error rate of the configuration, see [`Index::set_error_rate`]. Documents ingested before a change keep their original error rate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// let index = Index::new(0.001);
+    /// assert_eq!(0.001, index.error_rate());
+    /// ```
+    pub fn error_rate(&self) -> f32 {
+        self.config.error_rate
+    }
+
+    /// Change the probability of false positive of the documents ingested from now on.
+    ///
+    /// Existing filters keep their original geometry, and so their probability of false positive, until their document is ingested again.
+    ///
+    /// # Errors
+    ///
+    /// If `rate` is not strictly between 0 and 1 then an error is returned and the `Index` is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn configure_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.01);
+    /// index.set_error_rate(0.00001)?;
+    /// assert_eq!(0.00001, index.config().error_rate);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_error_rate(&mut self, rate: f32) -> Result<(), Error> {
+        if !(rate > 0.0 && rate < 1.0) {
+            return Err(Error::InvalidErrorRate);
+        }
+        self.config.error_rate = rate;
+        Ok(())
+    }
+
+    /// Dump the `Index` in JSON format to `writer`.
+    ///
+    /// The dump can be restored with [`Index::restore`] or [`Index::try_restore`]. The writer is flushed once the dump is written.
+    ///
+    /// # Errors
+    ///
+    /// If the dump cannot be written an [`Error::Io`] is returned, and if the `Index` cannot be serialized an [`Error::Serialize`] is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn dump_index() -> Result<(), Error> {
+    /// let index = Index::new(0.00001);
+    /// let mut dump = Vec::new();
+    /// index.dump(&mut dump)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dump<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        serde_json::to_writer(&mut *writer, self).map_err(serialize_error)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Dump the configuration and the filters of the `Index` to `writer`, one JSON record per line.
+    ///
+    /// The first line holds the configuration, then each line holds the name and the filter of a document, in order of name.
+    /// Such a dump can be searched by [`Index::search_streaming_dump`] without being restored, keeping memory low for huge indexes.
+    /// The aliases, fields and tags of the documents are not dumped, use [`Index::dump`] to keep them.
+    ///
+    /// # Errors
+    ///
+    /// If the dump cannot be written an [`Error::Io`] is returned, and if the `Index` cannot be serialized an [`Error::Serialize`] is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::fs::File;
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn dump_index() -> Result<(), Error> {
+    /// let index = Index::new(0.00001);
+    /// index.dump_lines(&mut File::create("./index.jsonl")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dump_lines<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut writer = BufWriter::new(writer);
+        serde_json::to_writer(&mut writer, &self.config).map_err(serialize_error)?;
+        writer.write_all(b"\n")?;
+        let names: BTreeSet<&K> = self.bloom_filters.keys().collect();
+        for name in names {
+            let record = DumpRecord { name, filter: self.bloom_filters[name].as_ref() };
+            serde_json::to_writer(&mut writer, &record).map_err(serialize_error)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(writer.flush()?)
+    }
+
+    /// Dump the `Index` in JSON format to the file at `path`, which is replaced atomically.
+    ///
+    /// The dump is written to a temporary file beside `path`, named after it with a `.tmp` suffix, then renamed to `path`:
+    /// a crash while dumping leaves the previous dump untouched instead of a truncated one.
+    ///
+    /// # Errors
+    ///
+    /// If the temporary file cannot be written or renamed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn dump_index() -> Result<(), Error> {
+    /// let index = Index::new(0.00001);
+    /// index.dump_to_file("./index.json")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dump_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let temporary_path = temporary_dump_path(path);
+        let result = (|| {
+            let mut writer = BufWriter::new(File::create(&temporary_path)?);
+            self.dump(&mut writer)?;
+            writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+            Ok(fs::rename(&temporary_path, path)?)
+        })();
+        if result.is_err() {
+            // The temporary file may not exist, there is nothing more to do if removing it fails
+            let _ = fs::remove_file(&temporary_path);
+        }
+        result
+    }
+
+    fn restore_replacing_config(content: &str, config: IndexConfig, check: bool) -> Result<Self, Error> {
+        let mut index: Index<K> = serde_json::from_str(content)?;
+        let dumped_fingerprint = index.tokenizer_fingerprint.take().unwrap_or_else(|| index.config.tokenizer_fingerprint());
+        if check && dumped_fingerprint != config.tokenizer_fingerprint() {
+            return Err(Error::IncompatibleConfig);
+        }
+        index.tokenizer_fingerprint = Some(dumped_fingerprint);
+        index.config = config;
+        Ok(index)
+    }
+
+    /// Check whether the filters were built with the tokenizer configuration of the `Index`.
+    ///
+    /// This is only false for an `Index` restored by [`Index::restore_with_config_unchecked`] with a different tokenizer configuration
+    /// (see [`IndexConfig::tokenizer_fingerprint`]): searches may then miss documents whose words were normalized differently.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn restore_index() -> Result<(), Error> {
+    /// let index_dump = "{\"error_rate\":0.1,\"preserve_accents\":true,\"bloom_filters\":{}}";
+    /// let index = Index::restore_with_config_unchecked(&index_dump, IndexConfig::new(0.01))?;
+    /// assert!(!index.tokenizer_fingerprint_matches());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tokenizer_fingerprint_matches(&self) -> bool {
+        self.tokenizer_fingerprint.as_ref().is_none_or(|fingerprint| *fingerprint == self.config.tokenizer_fingerprint())
+    }
+
+    /// Split the `Index` into its error rate and the filters of its documents, by name, for a custom persistence.
+    ///
+    /// The other options of the configuration, the fields, tags and insertion order of documents are discarded,
+    /// and the `Index` is not dumped even if [`Index::auto_dump_on_drop`] was called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn split_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// let (error_rate, filters) = index.into_parts();
+    /// let index = Index::from_parts(error_rate, filters);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_parts(mut self) -> (f32, HashMap<K, BloomFilter>) {
+        // The Index is dropped once its filters are taken, which must not dump an empty Index
+        self.auto_dump_path = None;
+        let filters = mem::take(&mut self.bloom_filters).into_iter()
+            .map(|(name, filter)| (name, Arc::try_unwrap(filter).unwrap_or_else(|filter| filter.as_ref().clone())))
+            .collect();
+        (self.config.error_rate, filters)
+    }
+
+    /// Check every filter of the `Index`, for instance after it was restored from an untrusted dump.
+    ///
+    /// Each filter is verified (see [`BloomFilter::verify`]) and searched once, so that a corrupted filter is detected at load time rather than by the first search.
+    /// Documents are checked in order of name, and the first problem found is returned.
+    ///
+    /// # Errors
+    ///
+    /// If a filter is corrupted or a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn restore_index() -> Result<(), Error> {
+    /// let index_dump = "{\"error_rate\":0.1,\"bloom_filters\":{\"file1.txt\":{\"key_size\":4,\"bitfield\":[8,130,65,18,131,164],\"bitfield_size\":48}}}";
+    /// let index = Index::restore(&index_dump);
+    /// index.warm_up()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn warm_up(&self) -> Result<(), Error> {
+        let filters: BTreeMap<&K, &BloomFilter> = self.bloom_filters.iter().map(|(name, filter)| (name, filter.as_ref())).collect();
+        for filter in filters.values() {
+            filter.verify()?;
+            filter.contains("")?;
+        }
+        for fields in self.fields.values() {
+            for filter in fields.values() {
+                filter.verify()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Dump the `Index` to the file at `path` when it is dropped.
+    ///
+    /// The dump is written atomically with [`Index::dump_to_file`] and can be restored with [`Index::restore`]. As errors cannot be returned while dropping, they are printed on the standard error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use index_bloom::Index;
+    /// let mut index = Index::new(0.00001);
+    /// index.auto_dump_on_drop("./index.json");
+    /// ```
+    pub fn auto_dump_on_drop<P: Into<PathBuf>>(&mut self, path: P) {
+        self.auto_dump_path = Some(path.into());
+    }
+
+    /// Ingest a new document.
+    ///
+    /// Insert each word of `content` in the index and identifies them under the given `name`.
+    /// To ingest the same key twice will replace its content in the `Index`.
+    ///
+    /// A content without any indexed word, such as an empty or punctuation only content, is stored as an empty filter which matches no keyword.
+    /// Such documents can be removed with [`Index::prune_empty`].
+    ///
+    /// # Errors
+    ///
+    /// If the content exceeds the maximum size of the configuration (see [`IndexConfig::with_max_content_bytes`]) or a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// let first_content = "A very very long content...";
+    /// index.ingest("foo".to_string(), first_content)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest(&mut self, name: K, content: &str) -> Result<(), Error> {
+        self.check_content_size(content.len())?;
+        if !self.config.share_filters {
+            let filter = self.build_filter(content)?;
+            self.store(name, filter);
+            return Ok(());
+        }
+        let digest = content_digest(content);
+        let filter = match self.shared_filters.get(&digest).and_then(Weak::upgrade) {
+            Some(filter) => filter,
+            None => {
+                let filter = Arc::new(self.build_filter(content)?);
+                self.shared_filters.retain(|_, filter| filter.strong_count() > 0);
+                self.shared_filters.insert(digest, Arc::downgrade(&filter));
+                filter
+            }
+        };
+        self.store_shared(name, filter);
+        Ok(())
+    }
+
+    /// Ingest a new document with categorical tags, such as `"lang:rust"`.
+    ///
+    /// Works like [`Index::ingest`], and stores the `tags` of the document so that [`Index::search_tagged`] can narrow results to the documents having some tags.
+    /// Unlike words, tags are stored as is in the `Index` and its dump: they never match by false positive.
+    /// Ingesting the document again without tags removes them.
+    ///
+    /// # Errors
+    ///
+    /// If the content exceeds the maximum size of the configuration or a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_with_tags("foo".to_string(), "A very very long content...", vec!["lang:en".to_string()])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_with_tags(&mut self, name: K, content: &str, tags: Vec<String>) -> Result<(), Error> {
+        self.check_content_size(content.len())?;
+        let filter = self.build_filter(content)?;
+        let name = self.store(name, filter);
+        if !tags.is_empty() {
+            self.tags.insert(name, tags.into_iter().collect());
+        }
+        Ok(())
+    }
+
+    /// Ingest a new document and report statistics about its words.
+    ///
+    /// Works like [`Index::ingest`], and returns [`IngestStats`] counting the words left empty by normalization, which would otherwise vanish silently,
+    /// and telling whether the filter of the document was clamped.
+    /// The content is tokenized a second time to count them, and words tokenized by a custom analyzer (see [`Index::set_analyzer`]) are not counted.
+    ///
+    /// # Errors
+    ///
+    /// If the content exceeds the maximum size of the configuration or a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// let stats = index.ingest_with_stats("foo".to_string(), "I ♥ Rust")?;
+    /// assert_eq!(1, stats.empty_words);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_with_stats(&mut self, name: K, content: &str) -> Result<IngestStats, Error> {
+        let clamped = self.config.clamp_bitfield && self.bitfield_limit_exceeded(self.aggregate_tokens(content).len()).is_some();
+        self.ingest(name, content)?;
+        let empty_words = match self.analyzer {
+            Some(_) => 0,
+            None => {
+                let text = self.visible_text(content);
+                let mut tokens = self.content_tokens(&text);
+                tokens.by_ref().for_each(drop);
+                tokens.empty_words()
+            }
+        };
+        Ok(IngestStats { empty_words, clamped })
+    }
+
+    /// Ingest a new document using several threads.
+    ///
+    /// Works like [`Index::ingest`], but the content is split in chunks of lines tokenized by up to `threads` threads, then the unique words are hashed by as many threads.
+    /// The filter built this way is identical to the one built by [`Index::ingest`], this is only worth it for very large documents.
+    ///
+    /// This method requires the `parallel` feature.
+    ///
+    /// # Errors
+    ///
+    /// If the content exceeds the maximum size of the configuration or a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_parallel("foo".to_string(), "A very very long content...\nOn many lines", 4)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn ingest_parallel(&mut self, name: K, content: &str, threads: usize) -> Result<(), Error> {
+        self.check_content_size(content.len())?;
+        let index: &Index<K> = self;
+        let threads = threads.max(1);
+        let limit = index.config.max_unique_terms.unwrap_or(usize::MAX);
+        let lines: Vec<&str> = content.lines().collect();
+        // Each chunk keeps its first unique tokens in order, so merging chunks in order gives the same tokens as a serial pass
+        let chunks_tokens: Vec<Vec<String>> = thread::scope(|scope| {
+            let workers: Vec<_> = lines.chunks(lines.len().div_ceil(threads).max(1))
+                .map(|chunk| scope.spawn(move || unique_tokens(chunk.iter().flat_map(|line| index.line_tokens(line)), limit)))
+                .collect();
+            workers.into_iter().map(|worker| worker.join().expect("Tokenization thread panicked")).collect()
+        });
+        let tokens = unique_tokens(chunks_tokens.into_iter().flatten(), limit);
+        let mut filter = index.empty_filter(tokens.len())?;
+        let chunks_filters: Vec<Result<BloomFilter, Error>> = thread::scope(|scope| {
+            let workers: Vec<_> = tokens.chunks(tokens.len().div_ceil(threads).max(1))
+                .map(|chunk| {
+                    let mut chunk_filter = filter.clone();
+                    scope.spawn(move || {
+                        for token in chunk {
+                            chunk_filter.insert(token)?;
+                        }
+                        Ok(chunk_filter)
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().expect("Hashing thread panicked")).collect()
+        });
+        for chunk_filter in chunks_filters {
+            filter.merge(chunk_filter?);
+        }
+        self.store(name, filter);
+        Ok(())
+    }
+
+    /// Ingest a new document from words tokenized beforehand.
+    ///
+    /// Each token is inserted as is, without going through the tokenizer nor the options of the [`IndexConfig`], except the maximum number of unique words.
+    /// Such a document is meant to be searched with [`Index::search_terms`]. To ingest the same key twice will replace its content in the `Index`.
+    ///
+    /// # Errors
+    ///
+    /// If a token cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_tokens("foo".to_string(), vec!["Métro", "C++"])?;
+    /// assert_eq!(vec!["foo"], index.search_terms(vec!["C++"])?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_tokens<I, S>(&mut self, name: K, tokens: I) -> Result<(), Error>
+        where I: IntoIterator<Item = S>, S: AsRef<str> {
+        let limit = self.config.max_unique_terms.unwrap_or(usize::MAX);
+        let tokens = unique_tokens(tokens.into_iter().map(|token| token.as_ref().to_string()), limit);
+        let mut filter = self.empty_filter(tokens.len())?;
+        for token in &tokens {
+            filter.insert(token)?;
+        }
+        self.store(name, filter);
+        Ok(())
+    }
+
+    /// Ingest a new document made of named sections.
+    ///
+    /// Each section is a `(field, content)` pair, for instance `("title", "My title")`.
+    /// The whole document is searchable like any other document, and each field is also searchable on its own with [`Index::search_fields`].
+    /// Field names are case-insensitive. To ingest the same key twice will replace its content in the `Index`.
+    ///
+    /// # Errors
+    ///
+    /// If the content of all sections exceeds the maximum size of the configuration or a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_sections("foo".to_string(), &[("title", "Rust"), ("body", "A very very long content...")])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_sections(&mut self, name: K, sections: &[(&str, &str)]) -> Result<(), Error> {
+        let content = sections.iter().map(|(_, content)| *content).collect::<Vec<&str>>().join("\n");
+        self.check_content_size(content.len())?;
+        let filter = self.build_filter(&content)?;
+        let mut field_contents: HashMap<String, Vec<&str>> = HashMap::new();
+        for (field, content) in sections {
+            field_contents.entry(field.to_lowercase()).or_default().push(content);
+        }
+        let mut field_filters = HashMap::new();
+        for (field, contents) in field_contents {
+            let field_content = contents.join("\n");
+            if !self.tokens(&field_content).is_empty() {
+                field_filters.insert(field, self.build_filter(&field_content)?);
+            }
+        }
+        let name = self.store(name, filter);
+        self.fields.insert(name, field_filters);
+        Ok(())
+    }
+
+    /// Ingest a new document from a JSON object, indexing the given string `fields` as sections.
+    ///
+    /// Each field is a path in the object, with dots separating nested keys, for instance `"author.name"`.
+    /// The string found at each path is ingested as a section named after the path (see [`Index::ingest_sections`]), so it is searchable with [`Index::search_fields`].
+    /// Missing fields and fields whose value is not a string are skipped.
+    ///
+    /// # Errors
+    ///
+    /// If `json` is not valid JSON, the content of the fields exceeds the maximum size of the configuration or a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// let json = r#"{"title": "Rust", "author": {"name": "Ferris"}, "stars": 5}"#;
+    /// index.ingest_json("foo".to_string(), json, &["title", "author.name", "stars"])?;
+    /// assert_eq!(vec!["foo"], index.search_fields("author.name:ferris")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_json(&mut self, name: K, json: &str, fields: &[&str]) -> Result<(), Error> {
+        let document: serde_json::Value = serde_json::from_str(json).map_err(Error::Deserialize)?;
+        let sections: Vec<(&str, &str)> = fields.iter()
+            .filter_map(|field| {
+                let value = field.split('.').try_fold(&document, |value, key| value.get(key))?;
+                value.as_str().map(|content| (*field, content))
+            })
+            .collect();
+        self.ingest_sections(name, &sections)
+    }
+
+    /// Append content to an existing document.
+    ///
+    /// Insert each word of `content` in the filter of the document identified by `name`, or ingest a new document if there is none.
+    /// Filters are sized for their original content: if the appended words push the estimated false positive rate of a filter past the error rate of the `Index`, the filter has to grow.
+    /// Only filters retaining their words (see [`IndexConfig::with_retained_terms`]) can grow: their capacity is doubled, like a `Vec`, and their words inserted again.
+    /// A growing filter is bounded by [`IndexConfig::with_max_bitfield_bytes`] like a new one, and clamped to it with [`IndexConfig::with_clamped_bitfield`].
+    ///
+    /// # Errors
+    ///
+    /// If the appended content exceeds the maximum size of the configuration or a word in the content cannot be hashed then an error is returned.
+    /// If the filter would have to grow but does not retain its words, or would exceed the maximum size of its bitfield, an error is returned and the document is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::with_config(IndexConfig::new(0.00001).with_retained_terms());
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// index.append("foo", "And even more content")?;
+    /// assert_eq!(vec!["foo"], index.search("long more")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn append<Q: ToOwned<Owned = K> + ?Sized>(&mut self, name: &Q, content: &str) -> Result<(), Error> {
+        let name = self.document_name(name.to_owned());
+        if !self.bloom_filters.contains_key(&name) {
+            return self.ingest(name, content);
+        }
+        self.check_content_size(content.len())?;
+        let tokens = self.aggregate_tokens(content);
+        let error_rate = self.config.error_rate;
+        self.document_frequencies.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.shared_filters.clear();
+        self.invalidate_query_cache();
+        let filter = self.bloom_filters[&name].as_ref();
+        let mut grown_filter = filter.clone();
+        for token in &tokens {
+            grown_filter.insert(token)?;
+        }
+        if grown_filter.retains_terms() {
+            if grown_filter.false_positive_rate() > f64::from(error_rate) {
+                let capacity = grown_filter.terms().map_or(0, |terms| terms.len()) * 2;
+                let clamped_size = self.bitfield_limit_exceeded(capacity).filter(|_| self.config.clamp_bitfield).map(|limit| limit * 8);
+                // A filter already clamped to the maximum size cannot get any better
+                if clamped_size != Some(grown_filter.bitfield_size()) {
+                    let mut resized_filter = self.empty_filter(capacity)?.with_empty_terms().with_bit_order(grown_filter.bit_order());
+                    for term in grown_filter.terms().into_iter().flatten() {
+                        resized_filter.insert(term)?;
+                    }
+                    grown_filter = resized_filter;
+                }
+            }
+        } else {
+            let false_positive_rate = grown_filter.false_positive_rate();
+            if false_positive_rate > f64::from(error_rate) && false_positive_rate > filter.false_positive_rate() {
+                return Err(Error::CapacityExceeded);
+            }
+        }
+        self.bloom_filters.insert(name, Arc::new(grown_filter));
+        Ok(())
+    }
+
+    /// Remove the document named `name` from the `Index`, returning whether it was indexed.
+    ///
+    /// The fields and tags of the document are removed too, as well as the aliases from or to its name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// assert!(index.remove("foo"));
+    /// assert_eq!(None, index.search("content")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remove<Q: ToOwned<Owned = K> + ?Sized>(&mut self, name: &Q) -> bool {
+        let name = self.document_name(name.to_owned());
+        let filter = match self.bloom_filters.remove(&name) {
+            Some(filter) => filter,
+            None => return false
+        };
+        self.document_frequencies.get_mut().unwrap_or_else(PoisonError::into_inner).retain(|token, frequency| {
+            match filter.contains(token) {
+                Ok(removed) => {
+                    *frequency -= usize::from(removed);
+                    true
+                },
+                Err(_) => false
+            }
+        });
+        self.insertion_order.remove(&name);
+        self.fields.remove(&name);
+        self.tags.remove(&name);
+        self.aliases.retain(|alias, canonical| *alias != name && *canonical != name);
+        self.invalidate_query_cache();
+        true
+    }
+
+    /// Remove the documents whose filter has no bit set, returning how many were removed.
+    ///
+    /// Such documents match no keyword, for instance documents ingested without any indexed word. They are removed like with [`Index::remove`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// index.ingest("bar".to_string(), "...")?;
+    /// assert_eq!(1, index.prune_empty());
+    /// assert_eq!(1, index.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prune_empty(&mut self) -> usize {
+        let empty: Vec<K> = self.bloom_filters.iter()
+            .filter(|(_, filter)| filter.bits_set() == 0)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &empty {
+            self.remove(name);
+        }
+        empty.len()
+    }
+
+    /// Remove every document from the `Index`, keeping its configuration.
+    ///
+    /// The fields, tags, aliases and insertion order of the documents are removed too, while the configuration, the analyzer and the automatic dump are kept,
+    /// so that the `Index` can be reused to index a new set of documents. The memory of the collections is kept as well, use [`Index::compact`] to reclaim it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// index.clear();
+    /// assert!(index.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear(&mut self) {
+        self.bloom_filters.clear();
+        self.insertion_order.clear();
+        self.next_insertion = 0;
+        self.fields.clear();
+        self.tags.clear();
+        self.aliases.clear();
+        self.document_frequencies.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.shared_filters.clear();
+        self.invalidate_query_cache();
+    }
+
+    /// Ignore new `stop_words` in the documents already ingested.
+    ///
+    /// The filter of every document is rebuilt without the given words, which are also added to the stop words of the configuration so that they are ignored by later ingestions and searches.
+    /// Stop words are normalized the same way as the content.
+    /// As a Bloom filter cannot forget a word, only filters retaining their words (see [`IndexConfig::with_retained_terms`]) can be rebuilt.
+    ///
+    /// # Errors
+    ///
+    /// If any filter does not retain its words, an error is returned and the `Index` is left unchanged.
+    /// If a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::with_config(IndexConfig::new(0.00001).with_retained_terms());
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// index.apply_stop_words(&vec!["very".to_string()].into_iter().collect::<HashSet<String>>())?;
+    /// assert_eq!(None, index.search("very")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn apply_stop_words(&mut self, stop_words: &HashSet<String>) -> Result<(), Error> {
+        let all_filters_retain_terms = self.bloom_filters.values().map(Arc::as_ref)
+            .chain(self.fields.values().flat_map(|fields| fields.values()))
+            .all(|filter| filter.retains_terms());
+        if !all_filters_retain_terms {
+            return Err(Error::TermsNotRetained);
+        }
+        let stop_tokens: HashSet<String> = stop_words.iter().flat_map(|word| Tokens::new(word)).collect();
+        let keep = |term: &String| {
+            let folded_term = match term.strip_prefix(ACCENT_NAMESPACE).and_then(|term| term.strip_prefix(' ')) {
+                Some(accented_term) => unidecode(accented_term),
+                None => term.clone()
+            };
+            !stop_tokens.contains(&folded_term)
+        };
+        let error_rate = self.config.error_rate;
+        let fingerprint_matches = self.tokenizer_fingerprint_matches();
+        self.document_frequencies.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.shared_filters.clear();
+        self.invalidate_query_cache();
+        for filter in self.bloom_filters.values_mut().map(Arc::make_mut).chain(self.fields.values_mut().flat_map(|fields| fields.values_mut())) {
+            filter.retain_terms(keep, error_rate)?;
+        }
+        self.config.stop_words.extend(stop_tokens);
+        if fingerprint_matches {
+            // The filters no longer hold the new stop words, so they match the new configuration
+            self.tokenizer_fingerprint = Some(self.config.tokenizer_fingerprint());
+        }
+        Ok(())
+    }
+
+    /// Remove a `term` from every document, returning how many documents were rebuilt without it and how many were skipped.
+    ///
+    /// The term is normalized the same way as the content, and its accented form and stem are removed too, unless another word of the document shares the stem.
+    /// The filters containing the term, including the filters of fields, are rebuilt without it, so that it stops matching any document.
+    /// As a Bloom filter cannot forget a word, only filters retaining their words (see [`IndexConfig::with_retained_terms`]) can be rebuilt:
+    /// the documents whose filter does not retain its words and may contain the term are skipped and counted in [`RemovalStats::skipped`].
+    ///
+    /// # Errors
+    ///
+    /// If a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn scrub_index() -> Result<(), Error> {
+    /// let mut index = Index::with_config(IndexConfig::new(0.00001).with_retained_terms());
+    /// index.ingest("foo".to_string(), "A very very long secret content...")?;
+    /// assert_eq!(1, index.global_remove_term("secret")?.removed);
+    /// assert_eq!(None, index.search("secret")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn global_remove_term(&mut self, term: &str) -> Result<RemovalStats, Error> {
+        let mut stats = RemovalStats::default();
+        let removed_tokens: HashSet<String> = self.raw_tokens(term).into_iter().collect();
+        if removed_tokens.is_empty() {
+            return Ok(stats);
+        }
+        let error_rate = self.config.error_rate;
+        self.document_frequencies.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.shared_filters.clear();
+        self.invalidate_query_cache();
+        let names: Vec<K> = self.bloom_filters.keys().cloned().collect();
+        for name in names {
+            let mut changed = false;
+            let mut retained = true;
+            let filters = self.bloom_filters.get_mut(&name).into_iter().map(Arc::make_mut)
+                .chain(self.fields.get_mut(&name).into_iter().flat_map(|fields| fields.values_mut()));
+            for filter in filters {
+                match scrub_terms(filter, &removed_tokens, error_rate)? {
+                    Some(scrubbed) => changed |= scrubbed,
+                    None => retained = false
+                }
+            }
+            if changed {
+                stats.removed += 1;
+            }
+            if !retained {
+                stats.skipped += 1;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Ingest a new document from a reader.
+    ///
+    /// Reads the whole content of `reader` and ingests it under the given `name` (see [`Index::ingest`]).
+    ///
+    /// # Errors
+    ///
+    /// If the content cannot be read, is not valid UTF-8, exceeds the maximum size of the configuration or a word cannot be hashed then an error is returned.
+    /// Content beyond the maximum size is never read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// let content = "A very very long content...".as_bytes();
+    /// index.ingest_reader("foo".to_string(), content)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_reader<R: Read>(&mut self, name: K, reader: R) -> Result<(), Error> {
+        // Reading one more byte than the limit is enough to detect an oversized content
+        let read_limit = self.config.max_content_bytes.map_or(u64::MAX, |limit| limit as u64 + 1);
+        let mut content = Vec::new();
+        reader.take(read_limit).read_to_end(&mut content)?;
+        // The size is checked first, as the limit may cut a character in the middle
+        self.check_content_size(content.len())?;
+        let content = String::from_utf8(content).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        self.ingest(name, &content)
+    }
+
+    /// Ingest a new document from a file.
+    ///
+    /// Reads the file at `path` and ingests its content under the given `name` (see [`Index::ingest`]).
+    /// With the `gzip` feature, files with a `.gz` extension are decompressed before being ingested.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read, is not valid UTF-8, exceeds the maximum size of the configuration or a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_file("foo".to_string(), "./foo.txt")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_file<P: AsRef<Path>>(&mut self, name: K, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        #[cfg(feature = "gzip")]
+        {
+            if path.extension().is_some_and(|extension| extension == "gz") {
+                return self.ingest_reader(name, GzDecoder::new(file));
+            }
+        }
+        self.ingest_reader(name, file)
+    }
+
+    /// Ingest a new document from a file too large to be held in memory.
+    ///
+    /// Works like [`Index::ingest_file`], except that the file is read twice, line by line: the first pass estimates the number of unique words
+    /// with a HyperLogLog estimator, then the second pass inserts the words in a filter sized from this estimate.
+    /// Neither the content nor its words are held in memory, except the first unique words up to the maximum number of the configuration if any.
+    /// As the estimate is accurate to about 1%, the probability of false positive of the filter may slightly differ from the one of [`Index::ingest_file`].
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read, is not valid UTF-8, exceeds the maximum size of the configuration or a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_file_two_pass("foo".to_string(), "./foo.txt")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ingest_file_two_pass<P: AsRef<Path>>(&mut self, name: K, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut estimator = HyperLogLog::new();
+        self.for_each_line(path, |line| {
+            self.estimate_line(&mut estimator, line);
+            Ok(())
+        })?;
+        let limit = self.config.max_unique_terms;
+        let mut filter = self.empty_filter(estimator.estimate().min(limit.unwrap_or(usize::MAX)))?;
+        let mut seen_tokens = HashSet::new();
+        self.for_each_line(path, |line| {
+            for token in self.line_tokens(line) {
+                if let Some(limit) = limit {
+                    // Only the first unique words are kept, like in Index::ingest
+                    if seen_tokens.len() >= limit || !seen_tokens.insert(token.clone()) {
+                        continue;
+                    }
+                }
+                filter.insert(&token)?;
+            }
+            Ok(())
+        })?;
+        self.store(name, filter);
+        Ok(())
+    }
+
+    /// Estimate the number of unique words of the content of `reader`, as indexed by [`Index::ingest`].
+    ///
+    /// The content is read line by line and its words counted with a HyperLogLog estimator, using a fixed amount of memory whatever the size of the content.
+    /// The estimate is accurate to about 1%, and exact for most contents of a few hundred words.
+    /// It ignores the maximum number of unique words of the configuration.
+    ///
+    /// # Errors
+    ///
+    /// If the content cannot be read, is not valid UTF-8 or exceeds the maximum size of the configuration then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let index = Index::new(0.00001);
+    /// let content = "A very very long content...".as_bytes();
+    /// assert_eq!(4, index.cardinality_estimate(content)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cardinality_estimate<R: Read>(&self, reader: R) -> Result<usize, Error> {
+        let mut estimator = HyperLogLog::new();
+        self.for_each_reader_line(reader, |line| {
+            self.estimate_line(&mut estimator, line);
+            Ok(())
+        })?;
+        Ok(estimator.estimate())
+    }
+
+    /// Search keywords in every documents.
+    ///
+    /// Splits `keywords` and searches for each word in all documents with a boolean AND.
+    /// The result may contain false positives (documents not containing all the keywords) according to an error rate set at the creation of the `Index` (see [`Index::new`]).
+    ///
+    /// If the configuration ignores every keyword, for instance a query made of a single stop word, the keywords are searched as is rather than matching nothing.
+    /// Such keywords only match documents ingested with a configuration which did not ignore them.
+    ///
+    /// Returns `None` when no document matches, which is always the case for an empty `Index` or keywords without any indexed word.
+    /// Use [`Index::iter`] to tell an empty `Index` from a query without match.
+    ///
+    /// # Errors
+    ///
+    /// If the query exceeds the hashing budget of the configuration (see [`IndexConfig::with_max_query_bytes`]) or a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let hits = index.search("content")?;
+    /// match hits {
+    ///      Some(documents) => {
+    ///          for doc in documents {
+    ///             println!("Found at {}", doc);
+    ///          }
+    ///      },
+    ///      None => println!("Not found")
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search(&self, keywords: &str) -> Result<Option<Vec<&K>>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        if let Some(documents) = self.cached_search(&tokens) {
+            return Ok(documents);
+        }
+        let documents = self.search_tokens(&tokens)?.map(|documents| self.collapse_aliases(documents));
+        self.cache_search(tokens, &documents);
+        Ok(documents)
+    }
+
+    /// Search keywords in every documents, recording for each document which words may be present in its filter.
+    ///
+    /// The trace explains the result of [`Index::search`]: a document is included when every word may be present.
+    /// Unlike a search, every word is tested in every filter, so a trace is much slower. Aliases are not collapsed, so that each document is traced under its own name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// for document in index.search_trace("long text")?.documents {
+    ///     println!("{} included: {} ({:?})", document.name, document.included, document.terms);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_trace(&self, keywords: &str) -> Result<SearchTrace<'_, K>, Error> {
+        let terms = self.query_tokens(keywords)?;
+        let mut documents = Vec::new();
+        for (name, filter) in &self.bloom_filters {
+            let mut document_terms = Vec::new();
+            for term in &terms {
+                document_terms.push((term.clone(), self.contains_token(filter, term)?));
+            }
+            let included = !terms.is_empty() && document_terms.iter().all(|(_, present)| *present);
+            documents.push(DocumentTrace { name, terms: document_terms, included });
+        }
+        documents.sort_by(|document_a, document_b| document_a.name.cmp(document_b.name));
+        Ok(SearchTrace { terms, documents })
+    }
+
+    /// Search documents containing any of the keywords.
+    ///
+    /// Works like [`Index::search`], except that a document matches as soon as one keyword may be present in it, rather than all of them.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// index.ingest("bar".to_string(), "A short text")?;
+    /// assert_eq!(vec!["bar", "foo"], index.search_any("content text")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_any(&self, keywords: &str) -> Result<Option<Vec<&K>>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let mut result: Vec<&K> = Vec::new();
+        for (name, filter) in &self.bloom_filters {
+            for token in &tokens {
+                if self.contains_token(filter, token)? {
+                    result.push(name);
+                    break;
+                }
+            }
+        }
+        if !result.is_empty() {
+            result.sort();
+            Ok(Some(self.collapse_aliases(result)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Search keywords in every documents and return a random sample of at most `sample_size` matching documents.
+    ///
+    /// The documents matching [`Index::search`] are sampled with a reservoir sample driven by a pseudo-random generator initialized with `seed`:
+    /// the same `seed` gives the same sample as long as the matching documents are the same. The sample is sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// for doc in index.search_sample("very long content", 10, 42)? {
+    ///     println!("{}", doc);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_sample(&self, keywords: &str, sample_size: usize, seed: u64) -> Result<Vec<&K>, Error> {
+        let mut generator = SplitMix64::new(seed);
+        let matches = self.search(keywords)?.unwrap_or_default();
+        let mut sample: Vec<&K> = Vec::with_capacity(sample_size.min(matches.len()));
+        for (position, name) in matches.into_iter().enumerate() {
+            if sample.len() < sample_size {
+                sample.push(name);
+            } else {
+                let replaced = generator.below(position + 1);
+                if replaced < sample_size {
+                    sample[replaced] = name;
+                }
+            }
+        }
+        sample.sort();
+        Ok(sample)
+    }
+
+    /// Register `alias` as another name of the document named `canonical`, for instance a symbolic link and its target.
+    ///
+    /// Both names can still be ingested, but the searches returning a list of names, such as [`Index::search`], [`Index::search_query`] or [`Index::search_fields`],
+    /// return the canonical name in place of the alias, once, so that the same document is not returned twice.
+    /// The searches returning a score or a count with each document, such as [`Index::search_ranked`], still return both names.
+    /// Registering an alias again replaces its canonical name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("target.txt".to_string(), "A very very long content...")?;
+    /// index.ingest("link.txt".to_string(), "A very very long content...")?;
+    /// index.register_alias("link.txt".to_string(), "target.txt".to_string());
+    /// assert_eq!(vec!["target.txt"], index.search("content")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_alias(&mut self, alias: K, canonical: K) {
+        let alias = self.document_name(alias);
+        let canonical = self.document_name(canonical);
+        self.aliases.insert(alias, canonical);
+        self.invalidate_query_cache();
+    }
+
+    /// Search keywords in every documents, keeping only the documents matching with a minimum confidence.
+    ///
+    /// Works like [`Index::search`], except that the confidence of each match, `1 - fpr^keywords` where `fpr` is the estimated false positive rate of the filter
+    /// (see [`BloomFilter::false_positive_rate`]), must be at least `min_confidence`, between 0 and 1.
+    /// This leaves out the matches of saturated filters, which are the most likely to be false positives.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let hits = index.search_min_confidence("very long content", 0.99)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_min_confidence(&self, keywords: &str, min_confidence: f64) -> Result<Option<Vec<&K>>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let documents: Vec<&K> = self.search_tokens(&tokens)?.unwrap_or_default().into_iter()
+            .filter(|name| 1.0 - self.bloom_filters[*name].false_positive_rate().powi(tokens.len() as i32) >= min_confidence)
+            .collect();
+        if !documents.is_empty() {
+            Ok(Some(self.collapse_aliases(documents)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Search keywords in every documents, ignoring the words of the query shorter than `min_len` characters.
+    ///
+    /// Works like [`Index::search`], except that short words, such as one character typos, are dropped from the query only:
+    /// unlike [`IndexConfig::with_min_token_length`], the content of documents is not affected. Lengths are counted once words are normalized.
+    /// If every word of the query is dropped, `None` is returned.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// assert_eq!(vec!["foo"], index.search_with_min_query_len("long x content", 2)?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_with_min_query_len(&self, keywords: &str, min_len: usize) -> Result<Option<Vec<&K>>, Error> {
+        let tokens: Vec<String> = self.query_tokens(keywords)?.into_iter()
+            .filter(|token| token.chars().count() >= min_len)
+            .collect();
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        let documents = self.search_tokens(&tokens)?;
+        Ok(documents.map(|documents| self.collapse_aliases(documents)))
+    }
+
+    /// Search keywords in every documents, along with the estimated number of unique words of each matching document.
+    ///
+    /// Works like [`Index::search`], except that each document comes with [`Index::estimated_terms`], so that shorter and more specific documents can be favored.
+    /// An empty `Vec` is returned when no document matches.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// for (name, terms) in index.search_annotated("content")? {
+    ///     println!("Found at {} with about {} words", name, terms);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_annotated(&self, keywords: &str) -> Result<Vec<(&K, usize)>, Error> {
+        let documents = self.search(keywords)?.unwrap_or_default();
+        Ok(documents.into_iter()
+            .map(|name| (name, self.bloom_filters[name].estimated_count()))
+            .collect())
+    }
+
+    /// Search words tokenized beforehand in every documents.
+    ///
+    /// Works like [`Index::search`], except that each term is searched as is, without going through the tokenizer nor the options of the [`IndexConfig`].
+    /// This is the counterpart of [`Index::ingest_tokens`].
+    ///
+    /// # Errors
+    ///
+    /// If the query exceeds the hashing budget of the configuration or a term cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let hits = index.search_terms(vec!["Métro", "C++"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_terms<I, S>(&self, terms: I) -> Result<Option<Vec<&K>>, Error>
+        where I: IntoIterator<Item = S>, S: AsRef<str> {
+        let tokens = unique_tokens(terms.into_iter().map(|term| term.as_ref().to_string()), usize::MAX);
+        self.check_query_budget(&tokens)?;
+        self.search_tokens(&tokens)
+    }
+
+    /// Search keywords in every documents, returning the matching documents as a set.
+    ///
+    /// Works like [`Index::search`], except that no match gives an empty set. The set is ordered by name and easy to combine with the results of other queries.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let rust_documents = index.search_set("rust")?;
+    /// let async_documents = index.search_set("async")?;
+    /// for doc in rust_documents.union(&async_documents) {
+    ///     println!("Found at {}", doc);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_set(&self, keywords: &str) -> Result<BTreeSet<&K>, Error> {
+        let documents = self.search(keywords)?.unwrap_or_default();
+        Ok(documents.into_iter().collect())
+    }
+
+    /// Search keywords in every documents, keeping only the documents having all the `required_tags`.
+    ///
+    /// Works like [`Index::search`], except that only the documents ingested with [`Index::ingest_with_tags`] and all the required tags are returned.
+    /// Tags are compared exactly, without normalization.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_with_tags("foo".to_string(), "A very very long content...", vec!["lang:en".to_string()])?;
+    /// index.ingest_with_tags("bar".to_string(), "Un contenu très long...", vec!["lang:fr".to_string()])?;
+    /// assert_eq!(vec!["foo"], index.search_tagged("long", &["lang:en"])?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_tagged(&self, keywords: &str, required_tags: &[&str]) -> Result<Option<Vec<&K>>, Error> {
+        self.filter_search(keywords, |name| {
+            required_tags.iter().all(|tag| self.tags.get(name).is_some_and(|tags| tags.contains(*tag)))
+        })
+    }
+
+    /// Search keywords given as raw bytes in every documents.
+    ///
+    /// Works like [`Index::search`] once `keywords` are validated as UTF-8 text.
+    ///
+    /// # Errors
+    ///
+    /// If the keywords are not valid UTF-8 or a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let hits = index.search_bytes(b"content")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_bytes(&self, keywords: &[u8]) -> Result<Option<Vec<&K>>, Error> {
+        let keywords = str::from_utf8(keywords)?;
+        self.search(keywords)
+    }
+
+    /// Search keywords in every documents, distinguishing accented characters.
+    ///
+    /// Works like [`Index::search`], except that `"café"` does not match `"cafe"`.
+    /// Only documents ingested with [`IndexConfig::with_preserved_accents`] can match.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::with_config(IndexConfig::new(0.00001).with_preserved_accents());
+    /// index.ingest("foo".to_string(), "Un café")?;
+    /// assert!(index.search("cafe")?.is_some());
+    /// assert!(index.search_strict("cafe")?.is_none());
+    /// assert!(index.search_strict("café")?.is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_strict(&self, keywords: &str) -> Result<Option<Vec<&K>>, Error> {
+        let mut tokens: Vec<String> = Vec::new();
+        for token in self.accented_tokens(keywords) {
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        self.check_query_budget(&tokens)?;
+        Ok(self.search_tokens(&tokens)?.map(|documents| self.collapse_aliases(documents)))
+    }
+
+    /// Search keywords in every documents, matching any word sharing the same stem.
+    ///
+    /// Works like [`Index::search`], except that keywords are reduced to their stem, so that `"apple"` matches `"apples"`.
+    /// Only documents ingested with [`IndexConfig::with_indexed_stems`] can match.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::with_config(IndexConfig::new(0.00001).with_indexed_stems());
+    /// index.ingest("foo".to_string(), "Red apples")?;
+    /// assert!(index.search("apple")?.is_none());
+    /// assert!(index.search_stemmed("apple")?.is_some());
+    /// assert!(index.search("apples")?.is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_stemmed(&self, keywords: &str) -> Result<Option<Vec<&K>>, Error> {
+        let mut tokens: Vec<String> = Vec::new();
+        for token in self.tokens(keywords).iter().map(|token| stem_token(token)) {
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        self.check_query_budget(&tokens)?;
+        Ok(self.search_tokens(&tokens)?.map(|documents| self.collapse_aliases(documents)))
+    }
+
+    /// Search a boolean expression of keywords in every documents.
+    ///
+    /// Keywords can be combined with the uppercase `AND` and `OR` operators and grouped with parentheses, as in `"(rust OR python) AND async"`.
+    /// Keywords without operator between them are combined with `AND`, which takes precedence over `OR`.
+    /// Keywords are normalized like with [`Index::search`]; ignored keywords, such as stop words, are removed from the expression.
+    ///
+    /// # Errors
+    ///
+    /// If the query is not a valid expression, for instance with unbalanced parentheses, parentheses nested more than 64 levels deep or an operator without operand, an error is returned.
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "Async programming in Rust")?;
+    /// index.ingest("bar".to_string(), "Async programming in Python")?;
+    /// assert_eq!(vec!["bar", "foo"], index.search_query("(rust OR python) AND async")?.unwrap());
+    /// assert_eq!(vec!["foo"], index.search_query("rust AND async")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_query(&self, query: &str) -> Result<Option<Vec<&K>>, Error> {
+        let query = match Query::parse(query)?.tokenize(&|word: &str| self.tokens(word)) {
+            Some(query) => query,
+            None => return Ok(None)
+        };
+        self.check_query_budget(query.terms())?;
+        let mut result :Vec<&K> = Vec::new();
+        for (name, filter) in &self.bloom_filters {
+            if query.matches(&|word| self.contains_token(filter, word))? {
+                result.push(name);
+            }
+        }
+        if !result.is_empty() {
+            result.sort();
+            Ok(Some(self.collapse_aliases(result)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Search keywords in every documents and report the cost of the query.
+    ///
+    /// Returns the same documents as [`Index::search`], along with [`SearchStats`] describing the work performed.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let (hits, stats) = index.search_with_stats("content")?;
+    /// println!("{} filters scanned with {} probes", stats.filters_scanned, stats.probes);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_with_stats(&self, keywords: &str) -> Result<(Option<Vec<&K>>, SearchStats), Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let mut stats = SearchStats::default();
+        let mut result :Vec<&K> = Vec::new();
+        if !tokens.is_empty() {
+            for (name, filter) in &self.bloom_filters {
+                stats.filters_scanned += 1;
+                let mut all_tokens_match = true;
+                for token in &tokens {
+                    if !self.probe_token(filter, token, &mut stats.probes)? {
+                        all_tokens_match = false;
+                        break;
+                    }
+                }
+                if all_tokens_match {
+                    result.push(name);
+                }
+            }
+        }
+        if !result.is_empty() {
+            result.sort();
+            let result = self.collapse_aliases(result);
+            stats.matches = result.len();
+            Ok((Some(result), stats))
+        } else {
+            Ok((None, stats))
+        }
+    }
+
+    /// Check whether any document matches all the keywords.
+    ///
+    /// Works like [`Index::search`], except that the scan stops at the first matching document.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// assert!(index.has_any_match("long content")?);
+    /// assert!(!index.has_any_match("short content")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn has_any_match(&self, keywords: &str) -> Result<bool, Error> {
+        let mut stats = SearchStats::default();
+        self.has_any_match_with_stats(keywords, &mut stats)
+    }
+
+    fn has_any_match_with_stats(&self, keywords: &str, stats: &mut SearchStats) -> Result<bool, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        if tokens.is_empty() {
+            return Ok(false);
+        }
+        for filter in self.bloom_filters.values() {
+            stats.filters_scanned += 1;
+            let mut all_tokens_match = true;
+            for token in &tokens {
+                if !self.probe_token(filter, token, &mut stats.probes)? {
+                    all_tokens_match = false;
+                    break;
+                }
+            }
+            if all_tokens_match {
+                stats.matches = 1;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Search keywords and their synonyms in every documents.
+    ///
+    /// Works like [`Index::search`], except that a keyword also matches when a document contains one of its synonyms (see [`IndexConfig::with_synonyms`]).
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let config = IndexConfig::new(0.00001).with_synonyms("car", vec!["automobile"]);
+    /// let mut index = Index::with_config(config);
+    /// index.ingest("foo".to_string(), "A red automobile")?;
+    /// assert_eq!(vec!["foo"], index.search_expanded("red car")?.unwrap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_expanded(&self, keywords: &str) -> Result<Option<Vec<&K>>, Error> {
+        let alternatives: Vec<Vec<String>> = self.query_tokens(keywords)?.into_iter()
+            .map(|token| {
+                let mut alternatives = self.config.synonyms.get(&token).cloned().unwrap_or_default();
+                alternatives.insert(0, token);
+                alternatives
+            })
+            .collect();
+        let mut result :Vec<&K> = Vec::new();
+        if !alternatives.is_empty() {
+            for (name, filter) in &self.bloom_filters {
+                let mut all_tokens_match = true;
+                for token_alternatives in &alternatives {
+                    if !self.contains_any_token(filter, token_alternatives)? {
+                        all_tokens_match = false;
+                        break;
+                    }
+                }
+                if all_tokens_match {
+                    result.push(name);
+                }
+            }
+        }
+        if !result.is_empty() {
+            result.sort();
+            Ok(Some(self.collapse_aliases(result)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn search_tokens(&self, tokens: &[String]) -> Result<Option<Vec<&K>>, Error> {
+        let mut result :Vec<&K> = Vec::new();
+        if let [token] = tokens {
+            // A single token is hashed once for all filters, rather than once per filter
+            let hashes = match self.bloom_filters.values().max_by_key(|filter| filter.key_size()).map_or(Ok(Vec::new()), |filter| filter.key_hashes(token, filter.key_size())) {
+                Err(Error::HashWord(_)) if self.config.hash_error_policy == HashErrorPolicy::FailOpen => return Ok(None),
+                hashes => hashes?
+            };
+            result.extend(self.bloom_filters.iter()
+                .filter(|(_, filter)| filter.contains_hashes(&hashes))
+                .map(|(name, _)| name));
+        } else if !tokens.is_empty() {
+            for (name, filter) in &self.bloom_filters {
+                if self.contains_all_tokens(filter, tokens)? {
+                    result.push(name);
+                }
+            }
+        }
+        if !result.is_empty() {
+            result.sort();
+            Ok(Some(result))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Suggest corrections of keywords for a query without match.
+    ///
+    /// Each keyword found in no document is corrected by deleting, inserting, replacing or swapping one character, and the corrections found in some documents are returned.
+    /// Suggestions are sorted by decreasing number of matching documents, then alphabetically, and at most `max` of them are returned.
+    /// No suggestion is returned when some documents match the query.
+    ///
+    /// # Errors
+    ///
+    /// If a word cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// assert_eq!(vec!["content"], index.suggest("contnet", 3)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn suggest(&self, keywords: &str, max: usize) -> Result<Vec<String>, Error> {
+        if self.has_any_match(keywords)? {
+            return Ok(Vec::new());
+        }
+        let mut suggestions: Vec<(usize, String)> = Vec::new();
+        for token in self.query_tokens(keywords)? {
+            if self.document_frequency(&token)? > 0 {
+                continue;
+            }
+            for correction in single_edits(&token) {
+                if !self.config.accepts(&correction) || suggestions.iter().any(|(_, suggestion)| *suggestion == correction) {
+                    continue;
+                }
+                let frequency = self.document_frequency(&correction)?;
+                if frequency > 0 {
+                    suggestions.push((frequency, correction));
+                }
+            }
+        }
+        suggestions.sort_by(|(frequency_a, suggestion_a), (frequency_b, suggestion_b)| {
+            frequency_b.cmp(frequency_a).then_with(|| suggestion_a.cmp(suggestion_b))
+        });
+        Ok(suggestions.into_iter().take(max).map(|(_, suggestion)| suggestion).collect())
+    }
+
+    /// Search keywords in every documents, after correcting the keywords with a `dictionary` of known words.
+    ///
+    /// Each keyword missing from the dictionary is replaced, independently of the others, by the first dictionary word in alphabetical order
+    /// at an edit distance of 1, deleting, inserting, replacing or swapping one character. Keywords without such a correction are searched as is.
+    /// The dictionary words are normalized the same way as the content. Unlike [`Index::suggest`], the documents are not consulted to correct the keywords.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// let dictionary: HashSet<String> = ["content", "long"].iter().map(|word| word.to_string()).collect();
+    /// let hits = index.search_with_dictionary("lnog contnet", &dictionary)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_with_dictionary(&self, keywords: &str, dictionary: &HashSet<String>) -> Result<Option<Vec<&K>>, Error> {
+        let known_tokens: HashSet<String> = dictionary.iter().flat_map(|word| self.raw_tokens(word)).collect();
+        let mut tokens: Vec<String> = Vec::new();
+        for token in self.query_tokens(keywords)? {
+            let token = if known_tokens.contains(&token) {
+                token
+            } else {
+                single_edits(&token).into_iter().find(|edit| known_tokens.contains(edit)).unwrap_or(token)
+            };
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        if let Some(documents) = self.cached_search(&tokens) {
+            return Ok(documents);
+        }
+        let documents = self.search_tokens(&tokens)?.map(|documents| self.collapse_aliases(documents));
+        self.cache_search(tokens, &documents);
+        Ok(documents)
+    }
+
+    /// Same as `BloomFilter::contains_all`, applying the hash error policy of the configuration.
+    fn contains_all_tokens(&self, filter: &BloomFilter, tokens: &[String]) -> Result<bool, Error> {
+        if self.config.hash_error_policy == HashErrorPolicy::FailClosed {
+            return filter.contains_all(tokens);
+        }
+        for token in tokens {
+            if !self.contains_token(filter, token)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Same as `BloomFilter::contains_any`, applying the hash error policy of the configuration.
+    fn contains_any_token(&self, filter: &BloomFilter, tokens: &[String]) -> Result<bool, Error> {
+        for token in tokens {
+            if self.contains_token(filter, token)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Same as `BloomFilter::contains`, applying the hash error policy of the configuration.
+    fn contains_token(&self, filter: &BloomFilter, token: &str) -> Result<bool, Error> {
+        let mut probes = 0;
+        self.probe_token(filter, token, &mut probes)
+    }
+
+    /// Same as `BloomFilter::probe`, applying the hash error policy of the configuration.
+    fn probe_token(&self, filter: &BloomFilter, token: &str, probes: &mut usize) -> Result<bool, Error> {
+        match filter.probe(token, probes) {
+            Err(Error::HashWord(_)) if self.config.hash_error_policy == HashErrorPolicy::FailOpen => Ok(false),
+            result => result
+        }
+    }
+
+    /// Replaces aliases by their canonical name in sorted `documents`, without duplicates.
+    fn collapse_aliases<'s>(&'s self, documents: Vec<&'s K>) -> Vec<&'s K> {
+        if self.aliases.is_empty() {
+            return documents;
+        }
+        let documents: BTreeSet<&K> = documents.into_iter()
+            .map(|name| self.aliases.get(name).unwrap_or(name))
+            .collect();
+        documents.into_iter().collect()
+    }
+
+    /// Returns the result of a previous search of `tokens`, if it is cached and not expired.
+    fn cached_search(&self, tokens: &[String]) -> Option<Option<Vec<&K>>> {
+        let mut cache = self.query_cache();
+        let cache = cache.as_mut()?;
+        let (created, documents) = cache.entries.get(tokens)?;
+        if created.elapsed() >= cache.ttl {
+            cache.entries.remove(tokens);
+            return None;
+        }
+        match documents {
+            Some(documents) => documents.iter()
+                .map(|name| self.bloom_filters.get_key_value(name).map(|(name, _)| name).or_else(|| self.aliases.values().find(|canonical| *canonical == name)))
+                .collect::<Option<Vec<&K>>>()
+                .map(Some),
+            None => Some(None)
+        }
+    }
+
+    fn cache_search(&self, tokens: Vec<String>, documents: &Option<Vec<&K>>) {
+        let mut cache = self.query_cache();
+        let cache = match cache.as_mut() {
+            Some(cache) => cache,
+            None => return
+        };
+        if cache.entries.len() >= cache.capacity && !cache.entries.contains_key(&tokens) {
+            let ttl = cache.ttl;
+            cache.entries.retain(|_, (created, _)| created.elapsed() < ttl);
+            if cache.entries.len() >= cache.capacity {
+                let oldest = cache.entries.iter().min_by_key(|(_, (created, _))| *created).map(|(tokens, _)| tokens.clone());
+                if let Some(oldest) = oldest {
+                    cache.entries.remove(&oldest);
+                }
+            }
+        }
+        let documents = documents.as_ref().map(|documents| documents.iter().map(|name| (*name).clone()).collect());
+        cache.entries.insert(tokens, (Instant::now(), documents));
+    }
+
+    fn query_cache(&self) -> MutexGuard<'_, Option<QueryCache<K>>> {
+        // The cache is always left consistent, even by a panicking thread
+        self.query_cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Forget the cached searches, as their results may have changed.
+    fn invalidate_query_cache(&mut self) {
+        if let Some(cache) = self.query_cache.get_mut().unwrap_or_else(PoisonError::into_inner) {
+            cache.entries.clear();
+        }
+    }
+
+    /// Same as `document_frequency`, computed once per token and kept up to date as documents are ingested.
+    fn cached_document_frequency(&self, token: &str) -> Result<usize, Error> {
+        if let Some(frequency) = self.cached_document_frequencies().get(token) {
+            return Ok(*frequency);
+        }
+        let frequency = self.document_frequency(token)?;
+        self.cached_document_frequencies().insert(token.to_string(), frequency);
+        Ok(frequency)
+    }
+
+    fn cached_document_frequencies(&self) -> MutexGuard<'_, HashMap<String, usize>> {
+        // The cache is always left consistent, even by a panicking thread
+        self.document_frequencies.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of documents which may contain `token`.
+    fn document_frequency(&self, token: &str) -> Result<usize, Error> {
+        let mut frequency = 0;
+        for filter in self.bloom_filters.values() {
+            if self.contains_token(filter, token)? {
+                frequency += 1;
+            }
+        }
+        Ok(frequency)
+    }
+
+    /// Check whether a term may be present in any document.
+    ///
+    /// The `term` is normalized the same way as the keywords of [`Index::search`], and only its first word is considered.
+    /// Unlike a search, the lookup stops as soon as one document reports the term.
+    ///
+    /// # Errors
+    ///
+    /// If the term cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// if index.term_exists("content")? {
+    ///     println!("At least one document may contain the term");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn term_exists(&self, term: &str) -> Result<bool, Error> {
+        let token = match self.tokens(term).into_iter().next() {
+            Some(token) => token,
+            None => return Ok(false)
+        };
+        self.check_query_budget(Some(&token))?;
+        for filter in self.bloom_filters.values() {
+            if self.contains_token(filter, &token)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the estimated number of unique words of the document named `name`, or `None` if it is not indexed.
+    ///
+    /// The estimate is computed from the number of bits set in the filter of the document, so it includes the accented forms and stems indexed
+    /// with [`IndexConfig::with_preserved_accents`] and [`IndexConfig::with_indexed_stems`]. A filter with every bit set estimates `usize::MAX`.
+    pub fn estimated_terms<Q: ToOwned<Owned = K> + ?Sized>(&self, name: &Q) -> Option<usize> {
+        let name = self.document_name(name.to_owned());
+        self.bloom_filters.get(&name).map(|filter| filter.estimated_count())
+    }
+
+    /// Returns the estimated probability that a word found in no document still matches at least one document.
+    ///
+    /// `samples` random words, drawn by a pseudo-random generator initialized with `seed`, are tested against every filter and the fraction matching any of them is returned.
+    /// This measures the quality of the whole `Index`: it grows with the number of documents and the saturation of their filters,
+    /// while [`BloomFilter::false_positive_rate`] only estimates the probability of false positive of a single document. An empty `Index` or 0 samples estimate 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn diagnose_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "A very very long content...")?;
+    /// println!("{:.4}% of unknown words match a document", index.collisions_estimate(10_000, 42) * 100.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn collisions_estimate(&self, samples: usize, seed: u64) -> f64 {
+        if samples == 0 || self.bloom_filters.is_empty() {
+            return 0.0;
+        }
+        let key_size = self.bloom_filters.values().map(|filter| filter.key_size()).max().unwrap_or(0);
+        let mut generator = SplitMix64::new(seed);
+        let mut collisions = 0;
+        for _ in 0..samples {
+            // Tokens never contain a control character, so the sample cannot be an indexed word
+            let sample = format!("\u{1}{:016x}", generator.next_u64());
+            if let Ok(hashes) = BloomFilter::word_hashes(&sample, key_size) {
+                if self.bloom_filters.values().any(|filter| filter.contains_hashes(&hashes)) {
+                    collisions += 1;
+                }
+            }
+        }
+        collisions as f64 / samples as f64
+    }
+
+    /// Search keywords in every documents and rank them by the number of matching keywords.
+    ///
+    /// Unlike [`Index::search`], a document is returned as soon as one keyword matches.
+    /// Each document comes with its number of matching keywords, the best matches first.
+    /// Documents with the same number of matching keywords are sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// for (doc, count) in index.search_ranked("very long content")? {
+    ///     println!("{} matches {} keywords", doc, count);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_ranked(&self, keywords: &str) -> Result<Vec<(&K, usize)>, Error> {
+        self.search_ranked_by(keywords, SecondarySort::default())
+    }
+
+    /// Search keywords in every documents and rank them by the number of matching keywords, with a custom `secondary_sort`.
+    ///
+    /// See [`Index::search_ranked`]. The `secondary_sort` orders documents matching the same number of keywords.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{Index, SecondarySort};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// let hits = index.search_ranked_by("very long content", SecondarySort::FalsePositiveRate)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_ranked_by(&self, keywords: &str, secondary_sort: SecondarySort) -> Result<Vec<(&K, usize)>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let mut result = self.match_counts(&tokens)?;
+        result.sort_by(|(name_a, count_a), (name_b, count_b)| {
+            count_b.cmp(count_a).then_with(|| self.secondary_order(name_a, name_b, secondary_sort))
+        });
+        Ok(result)
+    }
+
+    /// Search keywords in every documents and rank them by their matching keywords, some of them boosting the rank without being required.
+    ///
+    /// A keyword written `keyword^boost`, for instance `"async^2"`, is optional: a document does not need to contain it, but its score increases by `boost` if it does.
+    /// The other keywords are required like with [`Index::search`], and each one counts for 1 in the score. When every keyword is optional, a document is returned as soon as one of them matches.
+    /// A boost must be a positive number, otherwise the keyword is taken as is. Documents are sorted by descending score, then by name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the keywords cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest("foo".to_string(), "Rust async programming")?;
+    /// index.ingest("bar".to_string(), "Rust programming")?;
+    /// let hits = index.search_boosted("rust programming async^2")?;
+    /// assert_eq!(vec![(&"foo".to_string(), 4.0), (&"bar".to_string(), 2.0)], hits);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_boosted(&self, keywords: &str) -> Result<Vec<(&K, f64)>, Error> {
+        let mut required_tokens: Vec<String> = Vec::new();
+        let mut boosted_tokens: Vec<(String, f64)> = Vec::new();
+        for word in keywords.split_whitespace() {
+            let (term, boost) = split_boost(word);
+            for token in self.tokens(term) {
+                match boost {
+                    Some(boost) => boosted_tokens.push((token, boost)),
+                    None if !required_tokens.contains(&token) => required_tokens.push(token),
+                    None => ()
+                }
+            }
+        }
+        self.check_query_budget(required_tokens.iter().chain(boosted_tokens.iter().map(|(token, _)| token)))?;
+        let mut result: Vec<(&K, f64)> = Vec::new();
+        if required_tokens.is_empty() && boosted_tokens.is_empty() {
+            return Ok(result);
+        }
+        'documents: for (name, filter) in &self.bloom_filters {
+            for token in &required_tokens {
+                if !self.contains_token(filter, token)? {
+                    continue 'documents;
+                }
+            }
+            let mut score = required_tokens.len() as f64;
+            for (token, boost) in &boosted_tokens {
+                if self.contains_token(filter, token)? {
+                    score += boost;
+                }
+            }
+            if score > 0.0 {
+                result.push((name, score));
+            }
+        }
+        result.sort_by(|(name_a, score_a), (name_b, score_b)| {
+            score_b.partial_cmp(score_a).unwrap_or(Ordering::Equal).then_with(|| name_a.cmp(name_b))
+        });
+        Ok(result)
+    }
+
+    /// Search keywords in every documents and group them by their number of matching keywords.
+    ///
+    /// Like [`Index::search_ranked`], a document is returned as soon as one keyword matches: documents matching no keyword are left out.
+    /// Each number of matching keywords maps to the documents matching that many keywords, sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// for (count, docs) in index.search_buckets("very long content")? {
+    ///     println!("{} documents match {} keywords", docs.len(), count);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_buckets(&self, keywords: &str) -> Result<HashMap<usize, Vec<&K>>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let mut result: HashMap<usize, Vec<&K>> = HashMap::new();
+        for (name, count) in self.match_counts(&tokens)? {
+            result.entry(count).or_default().push(name);
+        }
+        for names in result.values_mut() {
+            names.sort();
+        }
+        Ok(result)
+    }
+
+    /// Search keywords in every documents and rank them by a score penalizing saturated filters.
+    ///
+    /// Like [`Index::search_ranked`], a document is returned as soon as one keyword matches.
+    /// Its score is the number of matching keywords divided by `1 + fill ratio` of its filter: a match in a sparse filter is more trustworthy than a match in a saturated one.
+    /// Documents are sorted by descending score, then by name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// for (doc, score) in index.search_scored("very long content")? {
+    ///     println!("{} scores {:.2}", doc, score);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_scored(&self, keywords: &str) -> Result<Vec<(&K, f64)>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let mut result: Vec<(&K, f64)> = self.match_counts(&tokens)?.into_iter()
+            .map(|(name, count)| (name, count as f64 / (1.0 + self.bloom_filters[name].fill_ratio())))
+            .collect();
+        result.sort_by(|(name_a, score_a), (name_b, score_b)| {
+            score_b.partial_cmp(score_a).unwrap_or(Ordering::Equal).then_with(|| name_a.cmp(name_b))
+        });
+        Ok(result)
+    }
+
+    /// Search keywords in every documents and rank them by the rarity of their matching keywords.
+    ///
+    /// Like [`Index::search_ranked`], a document is returned as soon as one keyword matches.
+    /// Its score is the sum of the inverse document frequency, `1 + ln((1 + documents) / (1 + matching documents))`, of each matching keyword:
+    /// a document matching a keyword found in few documents comes before a document matching a common one.
+    /// Documents are sorted by descending score, then by name.
+    ///
+    /// The number of documents matching each keyword is computed on the first query for this keyword, then kept up to date as documents are ingested.
+    /// It is based on the filters, so it accounts for false positives like any search.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// # let mut index = Index::new(0.00001);
+    /// for (doc, score) in index.search_by_rarity("very long content")? {
+    ///     println!("{} scores {:.2}", doc, score);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_by_rarity(&self, keywords: &str) -> Result<Vec<(&K, f64)>, Error> {
+        let tokens = self.query_tokens(keywords)?;
+        let documents_count = self.bloom_filters.len() as f64;
+        let mut rarities = Vec::new();
+        for token in &tokens {
+            let frequency = self.cached_document_frequency(token)? as f64;
+            rarities.push(1.0 + ((1.0 + documents_count) / (1.0 + frequency)).ln());
+        }
+        let mut result = Vec::new();
+        for (name, filter) in &self.bloom_filters {
+            let mut score = 0.0;
+            for (token, rarity) in tokens.iter().zip(&rarities) {
+                if self.contains_token(filter, token)? {
+                    score += rarity;
+                }
+            }
+            if score > 0.0 {
+                result.push((name, score));
+            }
+        }
+        result.sort_by(|(name_a, score_a), (name_b, score_b)| {
+            score_b.partial_cmp(score_a).unwrap_or(Ordering::Equal).then_with(|| name_a.cmp(name_b))
+        });
+        Ok(result)
+    }
+
+    /// Returns the unique tokens of `keywords` along with their optional field qualifier, checking the query budget.
+    fn qualified_tokens(&self, keywords: &str) -> Result<Vec<(Option<String>, String)>, Error> {
+        let mut qualified_tokens: Vec<(Option<String>, String)> = Vec::new();
+        for word in keywords.split_whitespace() {
+            let (field, term) = split_field(word);
+            for token in self.tokens(term) {
+                let qualified_token = (field.clone(), token);
+                if !qualified_tokens.contains(&qualified_token) {
+                    qualified_tokens.push(qualified_token);
+                }
+            }
+        }
+        self.check_query_budget(qualified_tokens.iter().map(|(_, token)| token))?;
+        Ok(qualified_tokens)
+    }
+
+    /// Returns the documents matching at least one of the `tokens`, with their number of matching tokens.
+    fn match_counts(&self, tokens: &[String]) -> Result<Vec<(&K, usize)>, Error> {
+        let mut result = Vec::new();
+        for (name, filter) in &self.bloom_filters {
+            let mut count = 0;
+            for token in tokens {
+                if self.contains_token(filter, token)? {
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                result.push((name, count));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Same as `search`, keeping only the documents for which `keep` returns `true`.
+    fn filter_search<F: Fn(&K) -> bool>(&self, keywords: &str, keep: F) -> Result<Option<Vec<&K>>, Error> {
+        let documents: Vec<&K> = self.search(keywords)?.unwrap_or_default().into_iter()
+            .filter(|name| keep(name))
+            .collect();
+        if !documents.is_empty() {
+            Ok(Some(documents))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn secondary_order(&self, name_a: &K, name_b: &K, secondary_sort: SecondarySort) -> Ordering {
+        let order = match secondary_sort {
+            SecondarySort::Name => Ordering::Equal,
+            SecondarySort::FalsePositiveRate => {
+                let rate_a = self.bloom_filters[name_a].false_positive_rate();
+                let rate_b = self.bloom_filters[name_b].false_positive_rate();
+                rate_a.partial_cmp(&rate_b).unwrap_or(Ordering::Equal)
+            },
+            SecondarySort::InsertionOrder => {
+                // Documents restored from a dump without insertion order come last
+                let order_a = self.insertion_order.get(name_a).unwrap_or(&u64::MAX);
+                let order_b = self.insertion_order.get(name_b).unwrap_or(&u64::MAX);
+                order_a.cmp(order_b)
+            }
+        };
+        order.then_with(|| name_a.cmp(name_b))
+    }
+
+    fn tokens(&self, text: &str) -> Vec<String> {
+        self.raw_tokens(text).into_iter().filter(|token| self.config.accepts(token)).collect()
+    }
+
+    /// Same as `tokens`, without ignoring the stop words and words of invalid length.
+    fn raw_tokens(&self, text: &str) -> Vec<String> {
+        let text = self.visible_text(text);
+        match &self.analyzer {
+            Some(analyzer) => analyzer.analyze(&text),
+            None => self.content_tokens(&text).collect()
+        }
+    }
+
+    /// The built-in tokenizer, with the locale and empty word policy of the configuration.
+    fn content_tokens<'t>(&self, text: &'t str) -> Tokens<'t> {
+        self.split_words(Tokens::new(text))
+            .with_locale(self.config.locale)
+            .with_empty_word_policy(self.config.empty_word_policy)
+    }
+
+    /// Splits the words of `tokens` on Unicode word boundaries if the configuration asks for it.
+    fn split_words<'t>(&self, tokens: Tokens<'t>) -> Tokens<'t> {
+        #[cfg(feature = "segmentation")]
+        if self.config.unicode_word_boundaries {
+            return tokens.with_word_boundaries();
+        }
+        tokens
+    }
+
+    /// Search keywords in every documents, with optional field qualifiers.
+    ///
+    /// Works like [`Index::search`], except that a keyword written `field:keyword` only matches in the given field of documents ingested with [`Index::ingest_sections`].
+    /// Keywords without qualifier match in any field.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::Index;
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::new(0.00001);
+    /// index.ingest_sections("foo".to_string(), &[("title", "Rust"), ("body", "Async programming")])?;
+    /// assert_eq!(vec!["foo"], index.search_fields("title:rust body:async")?.unwrap());
+    /// assert_eq!(None, index.search_fields("title:async")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_fields(&self, keywords: &str) -> Result<Option<Vec<&K>>, Error> {
+        let qualified_tokens = self.qualified_tokens(keywords)?;
+        let mut result :Vec<&K> = Vec::new();
+        if !qualified_tokens.is_empty() {
+            for (name, filter) in &self.bloom_filters {
+                let mut all_tokens_match = true;
+                for (field, token) in &qualified_tokens {
+                    let field_filter = match field {
+                        Some(field) => self.fields.get(name).and_then(|fields| fields.get(field)),
+                        None => Some(filter.as_ref())
+                    };
+                    let token_match = match field_filter {
+                        Some(field_filter) => self.contains_token(field_filter, token)?,
+                        None => false
+                    };
+                    if !token_match {
+                        all_tokens_match = false;
+                        break;
+                    }
+                }
+                if all_tokens_match {
+                    result.push(name);
+                }
+            }
+        }
+        if !result.is_empty() {
+            result.sort();
+            Ok(Some(self.collapse_aliases(result)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Search keywords in the fields of every documents and rank them by the score of their fields.
+    ///
+    /// Keywords are written like in [`Index::search_fields`]. The score of a field is its number of matching keywords, counting the keywords qualified with this field
+    /// and the keywords without qualifier, and the scores of the fields of a document are combined with the [`FieldCombiner`](crate::FieldCombiner) of the configuration.
+    /// A document ingested without sections is scored as a single field, matching only the keywords without qualifier.
+    /// A document is returned as soon as one keyword matches, and documents are sorted by descending score, then by name.
+    ///
+    /// # Errors
+    ///
+    /// If a word in the content cannot be hashed then an error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use index_bloom::{FieldCombiner, Index, IndexConfig};
+    /// # use index_bloom::Error;
+    /// # fn search_index() -> Result<(), Error> {
+    /// let mut index = Index::with_config(IndexConfig::new(0.00001).with_field_combiner(FieldCombiner::Sum));
+    /// index.ingest_sections("foo".to_string(), &[("title", "Rust"), ("body", "Rust programming")])?;
+    /// for (doc, score) in index.search_fields_ranked("rust title:programming")? {
+    ///     println!("{} scores {}", doc, score);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_fields_ranked(&self, keywords: &str) -> Result<Vec<(&K, f64)>, Error> {
+        let qualified_tokens = self.qualified_tokens(keywords)?;
+        let mut result: Vec<(&K, f64)> = Vec::new();
+        if qualified_tokens.is_empty() {
+            return Ok(result);
+        }
+        for (name, filter) in &self.bloom_filters {
+            let score = match self.fields.get(name).filter(|fields| !fields.is_empty()) {
+                Some(fields) => {
+                    let mut field_scores = Vec::with_capacity(fields.len());
+                    for (field, field_filter) in fields {
+                        let mut count = 0;
+                        for (qualifier, token) in &qualified_tokens {
+                            let applies = qualifier.as_ref().is_none_or(|qualifier| qualifier == field);
+                            if applies && self.contains_token(field_filter, token)? {
+                                count += 1;
+                            }
+                        }
+                        field_scores.push((field.as_str(), count as f64));
+                    }
+                    self.config.field_combiner.combine(field_scores)
+                },
+                None => {
+                    let mut count = 0;
+                    for (_, token) in qualified_tokens.iter().filter(|(qualifier, _)| qualifier.is_none()) {
+                        if self.contains_token(filter, token)? {
+                            count += 1;
+                        }
+                    }
+                    count as f64
+                }
+            };
+            if score > 0.0 {
+                result.push((name, score));
+            }
+        }
+        result.sort_by(|(name_a, score_a), (name_b, score_b)| {
+            score_b.partial_cmp(score_a).unwrap_or(Ordering::Equal).then_with(|| name_a.cmp(name_b))
+        });
+        Ok(result)
+    }
+
+    fn build_filter(&self, content: &str) -> Result<BloomFilter, Error> {
+        let tokens_agg = self.aggregate_tokens(content);
+        let mut filter = self.empty_filter(tokens_agg.len())?;
+        for token in tokens_agg {
+            filter.insert(&token)?;
+        }
+        Ok(filter)
+    }
+
+    /// Returns the maximum size of a bitfield, in bytes, if a filter sized for `capacity` words would exceed it.
+    fn bitfield_limit_exceeded(&self, capacity: usize) -> Option<usize> {
+        self.config.max_bitfield_bytes
+            .filter(|limit| BloomFilter::optimal_bitfield_size(capacity.max(1), self.config.error_rate).div_ceil(8) > *limit)
+    }
+
+    fn empty_filter(&self, capacity: usize) -> Result<BloomFilter, Error> {
+        // A content without any word gets the smallest filter, matching nothing
+        let capacity = capacity.max(1);
+        let mut filter = match self.bitfield_limit_exceeded(capacity) {
+            Some(limit) => {
+                if !self.config.clamp_bitfield {
+                    return Err(Error::FilterTooLarge { limit });
+                }
+                BloomFilter::with_bitfield_size(capacity, limit * 8)
+            },
+            _ => BloomFilter::new(capacity, self.config.error_rate)
+        };
+        if self.config.retain_terms {
+            filter = filter.with_empty_terms();
+        }
+        Ok(filter)
+    }
+
+    fn store(&mut self, name: K, filter: BloomFilter) -> K {
+        self.store_shared(name, Arc::new(filter))
+    }
+
+    fn store_shared(&mut self, name: K, filter: Arc<BloomFilter>) -> K {
+        let name = self.document_name(name);
+        let previous_filter = self.bloom_filters.get(&name);
+        self.document_frequencies.get_mut().unwrap_or_else(PoisonError::into_inner).retain(|token, frequency| {
+            // A token which cannot be hashed is dropped from the cache, and the error raised by the next search
+            match (previous_filter.map_or(Ok(false), |previous_filter| previous_filter.contains(token)), filter.contains(token)) {
+                (Ok(previous), Ok(current)) => {
+                    *frequency = *frequency + usize::from(current) - usize::from(previous);
+                    true
+                },
+                _ => false
+            }
+        });
+        self.fields.remove(&name);
+        self.tags.remove(&name);
+        self.insertion_order.insert(name.clone(), self.next_insertion);
+        self.next_insertion += 1;
+        self.bloom_filters.insert(name.clone(), filter);
+        self.invalidate_query_cache();
+        name
+    }
+
+    /// Calls `f` on each line of the file at `path`, decompressed with the `gzip` feature, without its line ending.
+    fn for_each_line<F: FnMut(&str) -> Result<(), Error>>(&self, path: &Path, f: F) -> Result<(), Error> {
+        let file = File::open(path)?;
+        let reader: Box<dyn Read> = {
+            #[cfg(feature = "gzip")]
+            {
+                if path.extension().is_some_and(|extension| extension == "gz") {
+                    Box::new(GzDecoder::new(file))
+                } else {
+                    Box::new(file)
+                }
+            }
+            #[cfg(not(feature = "gzip"))]
+            {
+                Box::new(file)
+            }
+        };
+        self.for_each_reader_line(reader, f)
+    }
+
+    /// Calls `f` on each line read from `reader`, without its line ending.
+    fn for_each_reader_line<R: Read, F: FnMut(&str) -> Result<(), Error>>(&self, reader: R, mut f: F) -> Result<(), Error> {
+        let read_limit = self.config.max_content_bytes.map_or(u64::MAX, |limit| limit as u64 + 1);
+        let mut reader = BufReader::new(reader.take(read_limit));
+        let mut line = String::new();
+        let mut size = 0;
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                return Ok(());
+            }
+            size += read;
+            self.check_content_size(size)?;
+            let content = match line.strip_suffix('\n') {
+                Some(content) => content.strip_suffix('\r').unwrap_or(content),
+                None => &line
+            };
+            f(content)?;
+        }
+    }
+
+    fn estimate_line(&self, estimator: &mut HyperLogLog, line: &str) {
+        for token in self.line_tokens(line) {
+            estimator.insert(&token);
+        }
+    }
+
+    fn check_content_size(&self, size: usize) -> Result<(), Error> {
+        match self.config.max_content_bytes {
+            Some(limit) if size > limit => Err(Error::ContentTooLarge { limit }),
+            _ => Ok(())
+        }
+    }
+
+    fn document_name(&self, name: K) -> K {
+        if self.config.normalize_names {
+            name.normalized()
+        } else {
+            name
+        }
+    }
+
+    fn accented_tokens(&self, text: &str) -> Vec<String> {
+        self.split_words(Tokens::accented(&self.visible_text(text)))
+            .with_locale(self.config.locale)
+            .filter(|token| self.config.accepts(&unidecode(token)))
+            .map(|token| tokens::namespaced(ACCENT_NAMESPACE, &token))
+            .collect()
+    }
+
+    fn visible_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.config.strip_invisible_characters {
+            Cow::Owned(tokens::strip_invisible(text))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    fn query_tokens(&self, keywords: &str) -> Result<Vec<String>, Error> {
+        let mut keywords_tokens = self.tokens(keywords);
+        if keywords_tokens.is_empty() {
+            // A query made only of ignored words, such as "the", searches them rather than nothing
+            keywords_tokens = self.raw_tokens(keywords);
+        }
+        let mut tokens: Vec<String> = Vec::new();
+        for token in keywords_tokens {
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        self.check_query_budget(&tokens)?;
+        Ok(tokens)
+    }
+
+    fn check_query_budget<'t, I: IntoIterator<Item = &'t String>>(&self, tokens: I) -> Result<(), Error> {
+        if let Some(limit) = self.config.max_query_bytes {
+            let size: usize = tokens.into_iter().map(|token| token.len()).sum();
+            if size > limit {
+                return Err(Error::QueryBudgetExceeded { limit });
+            }
+        }
+        Ok(())
+    }
+
+    fn aggregate_tokens(&self, content: &str) -> Vec<String> {
+        let limit = self.config.max_unique_terms.unwrap_or(usize::MAX);
+        unique_tokens(content.lines().flat_map(|line| self.line_tokens(line)), limit)
+    }
+
+    fn line_tokens(&self, line: &str) -> Vec<String> {
+        let mut tokens = self.tokens(line);
+        if self.config.index_stems {
+            let stems: Vec<String> = tokens.iter().map(|token| stem_token(token)).collect();
+            tokens.extend(stems);
+        }
+        if self.config.preserve_accents {
+            tokens.extend(self.accented_tokens(line));
+        }
+        tokens
+    }
+}
+
+impl<'a, K: DocumentKey> IntoIterator for &'a Index<K> {
+    type Item = (&'a K, DocumentStats);
+    type IntoIter = Iter<'a, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Compare the documents matching `keywords` in two snapshots of an `Index`.
+///
+/// Returns the documents matching in `new` but not in `old`, then the documents matching in `old` but not in `new`, both sorted by name.
+/// Each snapshot is searched like [`Index::search`], so false positives in either snapshot may show up as changes.
+///
+/// # Errors
+///
+/// If a word in the content cannot be hashed then an error is returned.
+///
+/// # Example
+///
+/// ```
+/// # use index_bloom::{diff_search, Index};
+/// # use index_bloom::Error;
+/// # fn search_index() -> Result<(), Error> {
+/// let mut old = Index::new(0.00001);
+/// old.ingest("foo".to_string(), "A very long content")?;
+/// let mut new = Index::new(0.00001);
+/// new.ingest("foo".to_string(), "A very short content")?;
+/// let (newly_matching, no_longer_matching) = diff_search(&old, &new, "long")?;
+/// assert!(newly_matching.is_empty());
+/// assert_eq!(vec!["foo"], no_longer_matching);
+/// # Ok(())
+/// # }
+/// ```
+pub fn diff_search<K: DocumentKey>(old: &Index<K>, new: &Index<K>, keywords: &str) -> Result<(Vec<K>, Vec<K>), Error> {
+    let old_documents = old.search_set(keywords)?;
+    let new_documents = new.search_set(keywords)?;
+    let newly_matching = new_documents.difference(&old_documents).map(|name| (*name).clone()).collect();
+    let no_longer_matching = old_documents.difference(&new_documents).map(|name| (*name).clone()).collect();
+    Ok((newly_matching, no_longer_matching))
+}
+
+/// Returns the words at an edit distance of 1 from `word`, in alphabetical order.
+fn single_edits(word: &str) -> BTreeSet<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut edits = BTreeSet::new();
+    for position in 0..=chars.len() {
+        let (head, tail) = chars.split_at(position);
+        if let Some((_, rest)) = tail.split_first() {
+            edits.insert(head.iter().chain(rest).collect());
+        }
+        if let [first, second, rest @ ..] = tail {
+            edits.insert(head.iter().chain([second, first]).chain(rest).collect());
+        }
+        for character in SUGGESTION_ALPHABET.chars() {
+            edits.insert(head.iter().chain([&character]).chain(tail).collect());
+            if let Some((_, rest)) = tail.split_first() {
+                edits.insert(head.iter().chain([&character]).chain(rest).collect());
+            }
+        }
+    }
+    edits.remove(word);
+    edits.remove("");
+    edits
+}
+
+/// Converts an error raised while writing JSON, telling write errors from serialization errors.
+fn serialize_error(error: serde_json::Error) -> Error {
+    if error.is_io() {
+        Error::Io(error.into())
+    } else {
+        Error::Serialize(error)
+    }
+}
+
+/// Returns the path of the temporary file written by [`Index::dump_to_file`] before being renamed to `path`.
+fn temporary_dump_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Returns the hexadecimal Blake2b digest of a content, identifying the documents whose filters can be shared.
+fn content_digest(content: &str) -> String {
+    let mut hasher = VarBlake2b::new(32).unwrap();
+    hasher.update(content);
+    let mut digest = String::new();
+    hasher.finalize_variable(|bytes| {
+        digest = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    });
+    digest
+}
+
+/// Serializes a map in order of keys, as the iteration order of a `HashMap` changes from one instance to another.
+fn serialize_sorted<S: Serializer, K: Ord + Serialize, V: Serialize>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<&K, &V>>().serialize(serializer)
+}
+
+/// Same as `serialize_sorted` for the fields of documents, sorting the fields of each document as well.
+fn serialize_sorted_fields<S: Serializer, K: Ord + Serialize>(fields: &HashMap<K, HashMap<String, BloomFilter>>, serializer: S) -> Result<S::Ok, S::Error> {
+    fields.iter()
+        .map(|(name, document_fields)| (name, document_fields.iter().collect::<BTreeMap<&String, &BloomFilter>>()))
+        .collect::<BTreeMap<&K, BTreeMap<&String, &BloomFilter>>>()
+        .serialize(serializer)
+}
+
+/// Returns the namespaced stem of `token`, as indexed with [`IndexConfig::with_indexed_stems`].
+fn stem_token(token: &str) -> String {
+    tokens::namespaced(STEM_NAMESPACE, &Stemmer::stem(token))
+}
+
+/// Rebuilds `filter` without the `removed` tokens, their accented forms and their stems if no other word of the filter shares them.
+///
+/// Returns whether the filter changed, or `None` if it may contain a removed token but does not retain its words.
+fn scrub_terms(filter: &mut BloomFilter, removed: &HashSet<String>, error_rate: f32) -> Result<Option<bool>, Error> {
+    let terms = match filter.terms() {
+        Some(terms) => terms,
+        None => {
+            for token in removed {
+                if filter.contains(token)? {
+                    return Ok(None);
+                }
+            }
+            return Ok(Some(false));
+        }
+    };
+    let kept_stems: HashSet<String> = terms.iter()
+        .filter(|term| !term.contains(' ') && !removed.contains(*term))
+        .map(|term| stem_token(term))
+        .collect();
+    let removed_stems: HashSet<String> = removed.iter()
+        .map(|token| stem_token(token))
+        .filter(|stem| !kept_stems.contains(stem))
+        .collect();
+    let is_removed = |term: &String| {
+        let folded_term = match term.strip_prefix(ACCENT_NAMESPACE).and_then(|term| term.strip_prefix(' ')) {
+            Some(accented_term) => unidecode(accented_term),
+            None => term.clone()
+        };
+        removed.contains(&folded_term) || removed_stems.contains(term)
+    };
+    if !terms.iter().any(is_removed) {
+        return Ok(Some(false));
+    }
+    filter.retain_terms(|term| !is_removed(term), error_rate)?;
+    Ok(Some(true))
+}
+
+/// Returns the first `limit` unique `tokens` in order of appearance.
+fn unique_tokens<I: IntoIterator<Item = String>>(tokens: I, limit: usize) -> Vec<String> {
+    let mut seen_tokens = HashSet::new();
+    let mut unique_tokens = Vec::new();
+    for token in tokens {
+        if unique_tokens.len() >= limit {
+            break;
+        }
+        if seen_tokens.insert(token.clone()) {
+            unique_tokens.push(token);
+        }
+    }
+    unique_tokens
+}
+
+/// Normalizes a document name used as a path: separators become `/`, `.` segments and repeated or trailing separators are removed.
+fn normalize_name(name: &str) -> String {
+    let path = name.replace('\\', "/");
+    let segments: Vec<&str> = path.split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    let normalized = segments.join("/");
+    if path.starts_with('/') {
+        format!("/{}", normalized)
+    } else {
+        normalized
+    }
+}
+
+/// Returns the first `depth` segments of a document name separated by `/`, or the whole name if it has less segments.
+fn name_prefix(name: &str, depth: usize) -> &str {
+    if depth == 0 {
+        return "";
+    }
+    match name.match_indices('/').nth(depth - 1) {
+        Some((position, _)) => &name[..position],
+        None => name
+    }
+}
+
+/// Splits a `field:term` query word into its field, in lowercase, and its term.
+fn split_field(word: &str) -> (Option<String>, &str) {
+    match word.split_once(':') {
+        Some((field, term)) if !field.is_empty() && !term.is_empty() => (Some(field.to_lowercase()), term),
+        _ => (None, word)
+    }
+}
+
+/// Splits a `term^boost` query word into its term and its boost, if it is a positive number.
+fn split_boost(word: &str) -> (&str, Option<f64>) {
+    match word.rsplit_once('^') {
+        Some((term, boost)) if !term.is_empty() => match boost.parse::<f64>() {
+            Ok(boost) if boost > 0.0 && boost.is_finite() => (term, Some(boost)),
+            _ => (word, None)
+        },
+        _ => (word, None)
+    }
+}
+
+impl<K: DocumentKey> Drop for Index<K> {
+    fn drop(&mut self) {
+        if let Some(path) = &self.auto_dump_path {
+            if let Err(error) = self.dump_to_file(path) {
+                eprintln!("Unable to dump index to {} : {}", path.display(), error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process, thread};
+    use crate::config::{EmptyWordPolicy, FieldCombiner, Locale};
+
+    #[test]
+    fn simple_content() {
+        let mut index = Index::new(0.01);
+        let content = "word1 word2\nword3\n\nword4";
+        index.ingest("simple_content.txt".to_string(), content).expect("Unable to ingest data");
+        assert!(index.search("word1").is_ok());
+        assert_eq!(vec!["simple_content.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(vec!["simple_content.txt"], index.search("word2").unwrap().unwrap());
+        assert_eq!(vec!["simple_content.txt"], index.search("word3").unwrap().unwrap());
+        assert_eq!(vec!["simple_content.txt"], index.search("word4").unwrap().unwrap());
+        assert_eq!(None, index.search("").unwrap());
+    }
+
+    #[test]
+    fn integer_document_keys() {
+        let mut index = Index::<u64>::keyed(IndexConfig::new(0.01).with_normalized_names());
+        index.ingest(3, "word1 word2").expect("Unable to ingest data");
+        index.ingest(1, "word1 word3").expect("Unable to ingest data");
+        index.ingest(20, "word4").expect("Unable to ingest data");
+        assert_eq!(vec![&1, &3], index.search("word1").unwrap().unwrap());
+        assert_eq!(vec![&3], index.search("word1 word2").unwrap().unwrap());
+        assert_eq!(vec![(&1, 1), (&3, 1), (&20, 1)], index.search_ranked("word1 word4").unwrap());
+        let dump = serde_json::to_string(&index).expect("Unable to serialize index");
+        let mut restored: Index<u64> = serde_json::from_str(&dump).expect("Unable to restore index");
+        assert_eq!(vec![&20], restored.search("word4").unwrap().unwrap());
+        assert!(restored.remove(&3));
+        assert_eq!(vec![&1], restored.search("word1").unwrap().unwrap());
+        assert_eq!(dump, serde_json::to_string(&index).expect("Unable to serialize index"));
+    }
+
+    #[test]
+    fn count_documents() {
+        let mut index = Index::new(0.01);
+        assert!(index.is_empty());
+        assert_eq!(0, index.len());
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert!(!index.is_empty());
+        assert_eq!(1, index.len());
+        index.ingest("file1.txt".to_string(), "word2").expect("Unable to ingest data");
+        assert_eq!(1, index.len());
+        assert!(index.remove("file1.txt"));
+        assert!(index.is_empty());
+        assert_eq!(0, index.len());
+    }
+
+    #[test]
+    fn contains_documents() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_normalized_names());
+        assert!(!index.contains_document("file1.txt"));
+        index.ingest("docs/file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert!(index.contains_document("docs/file1.txt"));
+        assert!(index.contains_document("./docs//file1.txt"));
+        assert!(!index.contains_document("file1.txt"));
+        assert!(index.remove("docs/file1.txt"));
+        assert!(!index.contains_document("docs/file1.txt"));
+    }
+
+    #[test]
+    fn list_documents() {
+        let mut index = Index::new(0.01);
+        assert_eq!(0, index.documents().count());
+        for name in &["file1.txt", "file2.txt", "file3.txt"] {
+            index.ingest(name.to_string(), "word1").expect("Unable to ingest data");
+        }
+        index.ingest("file2.txt".to_string(), "word2").expect("Unable to ingest data");
+        let expected: BTreeSet<&str> = vec!["file1.txt", "file2.txt", "file3.txt"].into_iter().collect();
+        assert_eq!(expected, index.documents().map(String::as_str).collect::<BTreeSet<&str>>());
+    }
+
+    #[test]
+    fn search_empty_index() {
+        let mut index = Index::new(0.01);
+        assert_eq!(None, index.search("word1").unwrap());
+        assert_eq!(None, index.search("").unwrap());
+        assert!(index.search_ranked("word1").unwrap().is_empty());
+        assert!(index.search_set("word1").unwrap().is_empty());
+        assert!(index.iter().next().is_none());
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(None, index.search("word2").unwrap());
+        assert!(index.iter().next().is_some());
+    }
+
+    #[test]
+    fn several_matches() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word2").unwrap().unwrap());
+        let expected = vec!["file1.txt", "file2.txt"];
+        assert_eq!(expected, index.search("word1").unwrap().unwrap());
+        assert_eq!(expected, index.search("word3").unwrap().unwrap());
+    }
+
+    #[test]
+    fn several_matches_with_any_keyword() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word4").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search_any("word2").unwrap().unwrap());
+        let expected = vec!["file1.txt", "file2.txt"];
+        assert_eq!(expected, index.search_any("word1").unwrap().unwrap());
+        assert_eq!(expected, index.search_any("word2 word3").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file3.txt"], index.search_any("word2 word4").unwrap().unwrap());
+        assert_eq!(None, index.search("word2 word4").unwrap());
+        assert_eq!(None, index.search_any("word5").unwrap());
+        assert_eq!(None, index.search_any("").unwrap());
+    }
+
+    #[test]
+    fn trace_search_decisions() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word4").expect("Unable to ingest data");
+        for keywords in &["word1", "word1 word2", "Word3 word1", "word5", ""] {
+            let trace = index.search_trace(keywords).unwrap();
+            let included: Vec<&String> = trace.documents.iter().filter(|document| document.included).map(|document| document.name).collect();
+            assert_eq!(index.search(keywords).unwrap().unwrap_or_default(), included, "{}", keywords);
+            assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], trace.documents.iter().map(|document| document.name).collect::<Vec<&String>>());
+        }
+        let trace = index.search_trace("word2 Word1").unwrap();
+        assert_eq!(vec!["word2", "word1"], trace.terms);
+        assert_eq!(vec![("word2".to_string(), false), ("word1".to_string(), true)], trace.documents[1].terms);
+        assert!(trace.documents[0].included);
+        assert!(!trace.documents[1].included);
+    }
+
+    #[test]
+    fn ingesting_twice_replace() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(None, index.search("word2").unwrap());
+        index.ingest("file1.txt".to_string(), "word2").expect("Unable to ingest data");
+        assert_eq!(None, index.search("word1").unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("word2").unwrap().unwrap());
+    }
+
+    #[test]
+    fn multi_keywords_search() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word1 word2").unwrap().unwrap());
+    }
+
+    #[test]
+    fn clean_keywords_before_search() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
         assert_eq!(vec!["file1.txt"], index.search("(word1) Word2, word3?").unwrap().unwrap());
     }
 
     #[test]
+    fn term_exists_in_any_document() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word3").expect("Unable to ingest data");
+        assert!(index.term_exists("word1").unwrap());
+        assert!(index.term_exists("Word3").unwrap());
+        assert!(!index.term_exists("word4").unwrap());
+        assert!(!index.term_exists("").unwrap());
+    }
+
+    #[cfg(feature = "segmentation")]
+    #[test]
+    fn search_with_unicode_word_boundaries() {
+        let content = "Hello,world! 東京タワー";
+        let mut default_index = Index::new(0.0001);
+        default_index.ingest("file1.txt".to_string(), content).expect("Unable to ingest data");
+        let config = IndexConfig::new(0.0001).with_unicode_word_boundaries();
+        assert_ne!(IndexConfig::new(0.0001).tokenizer_fingerprint(), config.tokenizer_fingerprint());
+        let mut index = Index::with_config(config);
+        index.ingest("file1.txt".to_string(), content).expect("Unable to ingest data");
+        assert_eq!(None, default_index.search("world").unwrap());
+        assert_eq!(vec!["file1.txt"], default_index.search("helloworld").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("world hello").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("京").unwrap().unwrap());
+        assert_eq!(None, index.search("helloworld").unwrap());
+        let restored: Index = serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+        assert_eq!(vec!["file1.txt"], restored.search("world").unwrap().unwrap());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_ingest_matches_serial_ingest() {
+        let content = (0..1000).map(|line| format!("line{} word{} Café", line, line % 7)).collect::<Vec<String>>().join("\n");
+        for config in [IndexConfig::new(0.01), IndexConfig::new(0.01).with_retained_terms().with_preserved_accents().with_max_unique_terms(500)] {
+            let mut serial_index = Index::with_config(config.clone());
+            serial_index.ingest("file1.txt".to_string(), &content).expect("Unable to ingest data");
+            let mut parallel_index = Index::with_config(config);
+            for threads in &[1, 3, 8] {
+                parallel_index.ingest_parallel("file1.txt".to_string(), &content, *threads).expect("Unable to ingest data");
+                assert_eq!(serial_index.bloom_filters["file1.txt"], parallel_index.bloom_filters["file1.txt"]);
+            }
+        }
+    }
+
+    #[test]
+    fn ingest_content_without_words() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_bitfield_bytes(2));
+        index.ingest("file1.txt".to_string(), "!!! ???").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), " \n\t ").expect("Unable to ingest data");
+        index.ingest_tokens("file4.txt".to_string(), Vec::<String>::new()).expect("Unable to ingest data");
+        index.ingest_reader("file5.txt".to_string(), &b"..."[..]).expect("Unable to ingest data");
+        assert_eq!(5, index.len());
+        assert!(index.iter().all(|(_, stats)| stats.bitfield_size == BloomFilter::new(1, 0.01).bitfield_size()));
+        assert_eq!(None, index.search("word1").unwrap());
+        assert_eq!(None, index.search("!!!").unwrap());
+        assert_eq!(None, index.search_any("word1 word2").unwrap());
+        assert!(index.search_ranked("word1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn prune_empty_documents() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "!!! ...").expect("Unable to ingest data");
+        index.ingest_tokens("file3.txt".to_string(), Vec::<String>::new()).expect("Unable to ingest data");
+        index.ingest("file4.txt".to_string(), "word3").expect("Unable to ingest data");
+        assert_eq!(2, index.prune_empty());
+        let mut names: Vec<&String> = index.documents().collect();
+        names.sort();
+        assert_eq!(vec!["file1.txt", "file4.txt"], names);
+        assert_eq!(0, index.prune_empty());
+    }
+
+    #[test]
+    fn clear_documents() {
+        let mut index = Index::with_config(IndexConfig::new(0.001).with_stop_words(vec!["the"]));
+        index.ingest("file1.txt".to_string(), "the word1 word2").expect("Unable to ingest data");
+        index.ingest_with_tags("file2.txt".to_string(), "word1", vec!["lang:en".to_string()]).expect("Unable to ingest data");
+        index.register_alias("file3.txt".to_string(), "file2.txt".to_string());
+        assert_eq!(2, index.search_by_rarity("word1").unwrap().len());
+        index.clear();
+        assert!(index.is_empty());
+        assert_eq!(None, index.search("word1").unwrap());
+        assert!(index.cached_document_frequencies().is_empty());
+        assert!(index.tags.is_empty());
+        assert!(index.aliases.is_empty());
+        index.ingest("file4.txt".to_string(), "the word3").expect("Unable to ingest data");
+        assert_eq!(0.001, index.config().error_rate);
+        assert_eq!(vec!["file4.txt"], index.search("word3").unwrap().unwrap());
+        assert_eq!(Some(&0), index.insertion_order.get("file4.txt"));
+        let geometry = BloomFilter::new(1, 0.001);
+        assert_eq!(geometry.key_size(), index.bloom_filters["file4.txt"].key_size());
+        assert_eq!(geometry.bitfield_size(), index.bloom_filters["file4.txt"].bitfield_size());
+    }
+
+    #[test]
+    fn sample_search_results() {
+        let mut index = Index::new(0.01);
+        for i in 0..50 {
+            index.ingest(format!("file{:02}.txt", i), "word1").expect("Unable to ingest data");
+        }
+        let sample = index.search_sample("word1", 10, 42).unwrap();
+        assert_eq!(10, sample.len());
+        assert_eq!(sample, index.search_sample("word1", 10, 42).unwrap());
+        assert_ne!(sample, index.search_sample("word1", 10, 43).unwrap());
+        assert_eq!(sample.len(), sample.iter().collect::<BTreeSet<_>>().len());
+        assert_eq!(index.search("word1").unwrap().unwrap(), index.search_sample("word1", 100, 42).unwrap());
+        assert_eq!(index.search("word1").unwrap().unwrap(), index.search_sample("word1", usize::MAX, 42).unwrap());
+        assert!(index.search_sample("word1", 0, 42).unwrap().is_empty());
+        assert!(index.search_sample("unknown", 10, 42).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cache_query_results() {
+        let mut index = Index::new(0.01);
+        index.set_query_cache(10, Duration::from_millis(300));
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word1").unwrap().unwrap());
+        // Replacing a filter behind the back of the index keeps the cached result
+        let mut filter = BloomFilter::new(1, 0.01);
+        filter.insert("word1").unwrap();
+        index.bloom_filters.insert("file2.txt".to_string(), Arc::new(filter));
+        assert_eq!(vec!["file1.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("WORD1").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_any("word1").unwrap().unwrap());
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search("word1").unwrap().unwrap());
+        index.ingest("file3.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search("word1").unwrap().unwrap());
+        assert!(index.remove("file3.txt"));
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(None, index.search("word2").unwrap());
+    }
+
+    #[test]
+    fn evict_oldest_cached_query() {
+        let mut index = Index::new(0.01);
+        index.set_query_cache(2, Duration::from_secs(60));
+        index.ingest("file1.txt".to_string(), "word1 word2 word3").expect("Unable to ingest data");
+        for keywords in &["word1", "word2", "word3"] {
+            index.search(keywords).unwrap();
+        }
+        let cached: BTreeSet<Vec<String>> = index.query_cache().as_ref().unwrap().entries.keys().cloned().collect();
+        assert_eq!(vec![vec!["word2".to_string()], vec!["word3".to_string()]].into_iter().collect::<BTreeSet<Vec<String>>>(), cached);
+        index.set_query_cache(0, Duration::from_secs(60));
+        index.search("word1").unwrap();
+        assert!(index.query_cache().is_none());
+    }
+
+    #[test]
+    fn boost_ranking_terms() {
+        let mut index = Index::new(0.0001);
+        index.ingest("file1.txt".to_string(), "rust async tokio").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "rust async serde").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "rust tokio serde").expect("Unable to ingest data");
+        let file1 = "file1.txt".to_string();
+        let file2 = "file2.txt".to_string();
+        let file3 = "file3.txt".to_string();
+        assert_eq!(vec![(&file1, 2.0), (&file2, 2.0)], index.search_boosted("rust async").unwrap());
+        assert_eq!(vec![(&file1, 4.0), (&file2, 2.0)], index.search_boosted("rust async tokio^2").unwrap());
+        assert_eq!(vec![(&file2, 2.5), (&file1, 2.0)], index.search_boosted("rust async serde^0.5").unwrap());
+        assert_eq!(vec![(&file3, 3.0), (&file1, 2.0), (&file2, 1.0)], index.search_boosted("tokio^2 serde^1").unwrap());
+        // A word without valid boost is a required word, caret included
+        assert!(index.search_boosted("rust async^0").unwrap().is_empty());
+        assert!(index.search_boosted("unknown rust^2").unwrap().is_empty());
+        assert!(index.search_boosted("").unwrap().is_empty());
+        assert_eq!(("term", Some(1.5)), split_boost("term^1.5"));
+        assert_eq!(("^2", None), split_boost("^2"));
+        assert_eq!(("term^-1", None), split_boost("term^-1"));
+    }
+
+    #[test]
+    fn estimate_collisions() {
+        let mut index = Index::new(0.001);
+        assert_eq!(0.0, index.collisions_estimate(1000, 42));
+        for i in 0..10 {
+            index.ingest(format!("file{}.txt", i), &format!("word{} common", i)).expect("Unable to ingest data");
+        }
+        let sparse_estimate = index.collisions_estimate(1000, 42);
+        assert!(sparse_estimate < 0.02, "sparse index estimated {}", sparse_estimate);
+        assert_eq!(sparse_estimate, index.collisions_estimate(1000, 42));
+        assert_eq!(0.0, index.collisions_estimate(0, 42));
+        let mut previous_estimate = sparse_estimate;
+        let mut filter = BloomFilter::new(10, 0.01);
+        for round in 0..3 {
+            for i in 0..20 {
+                filter.insert(&format!("saturating{}-{}", round, i)).unwrap();
+            }
+            index.bloom_filters.insert("saturated.txt".to_string(), Arc::new(filter.clone()));
+            let estimate = index.collisions_estimate(1000, 42);
+            assert!(estimate > previous_estimate, "{} is not above {}", estimate, previous_estimate);
+            previous_estimate = estimate;
+        }
+        assert!(previous_estimate > 0.5);
+    }
+
+    #[test]
+    fn remove_term_globally() {
+        let mut index = Index::with_config(IndexConfig::new(0.001).with_retained_terms().with_preserved_accents().with_indexed_stems());
+        index.ingest("file1.txt".to_string(), "token1 Secrét apples").expect("Unable to ingest data");
+        index.ingest_sections("file2.txt".to_string(), &[("title", "secret"), ("body", "token2")]).expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "token3 secrets").expect("Unable to ingest data");
+        assert_eq!(3, index.search_stemmed("secret").unwrap().unwrap().len());
+        assert_eq!(RemovalStats { removed: 2, skipped: 0 }, index.global_remove_term("SECRET").unwrap());
+        assert_eq!(None, index.search("secret").unwrap());
+        assert_eq!(None, index.search_strict("secrét").unwrap());
+        assert_eq!(None, index.search_fields("title:secret").unwrap());
+        assert_eq!(vec!["file3.txt"], index.search_stemmed("secret").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("token1 apples").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search_fields("body:token2").unwrap().unwrap());
+        assert_eq!(RemovalStats::default(), index.global_remove_term("secret").unwrap());
+        assert_eq!(RemovalStats::default(), index.global_remove_term("!!!").unwrap());
+        let mut plain_index = Index::new(0.001);
+        plain_index.ingest("file1.txt".to_string(), "token1 secret").expect("Unable to ingest data");
+        plain_index.ingest("file2.txt".to_string(), "token2").expect("Unable to ingest data");
+        assert_eq!(RemovalStats { removed: 0, skipped: 1 }, plain_index.global_remove_term("secret").unwrap());
+        assert_eq!(vec!["file1.txt"], plain_index.search("secret").unwrap().unwrap());
+    }
+
+    #[test]
+    fn remove_document() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest_with_tags("file2.txt".to_string(), "word1 word3", vec!["lang:en".to_string()]).expect("Unable to ingest data");
+        index.register_alias("file3.txt".to_string(), "file2.txt".to_string());
+        assert_eq!(2, index.search_by_rarity("word1").unwrap().len());
+        assert_eq!(Some(2), index.cached_document_frequencies().get("word1").copied());
+        assert!(index.remove("file2.txt"));
+        assert!(!index.remove("file2.txt"));
+        assert!(!index.remove("unknown.txt"));
+        assert_eq!(vec!["file1.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(None, index.search("word3").unwrap());
+        assert_eq!(Some(1), index.cached_document_frequencies().get("word1").copied());
+        assert!(index.tags.is_empty());
+        assert!(index.aliases.is_empty());
+        assert!(!index.insertion_order.contains_key("file2.txt"));
+    }
+
+    #[test]
+    fn append_grows_term_retaining_filters() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_retained_terms());
+        index.append("file1.txt", "word0").expect("Unable to append data");
+        let initial_size = index.bloom_filters["file1.txt"].bitfield_size();
+        for word in 1..100 {
+            index.append("file1.txt", &format!("word{}", word)).expect("Unable to append data");
+            assert!(index.bloom_filters["file1.txt"].false_positive_rate() <= 0.01);
+        }
+        assert!(index.bloom_filters["file1.txt"].bitfield_size() > initial_size);
+        for word in 0..100 {
+            assert_eq!(vec!["file1.txt"], index.search(&format!("word{}", word)).unwrap().unwrap());
+        }
+    }
+
+    #[test]
+    fn append_cannot_grow_plain_filters() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        let content = (0..50).map(|word| format!("other{}", word)).collect::<Vec<String>>().join(" ");
+        let filter = index.bloom_filters["file1.txt"].clone();
+        assert!(matches!(index.append("file1.txt", &content), Err(Error::CapacityExceeded)));
+        assert_eq!(filter, index.bloom_filters["file1.txt"]);
+        index.append("file1.txt", "word2").expect("Unable to append data");
+        assert_eq!(filter, index.bloom_filters["file1.txt"]);
+    }
+
+    #[test]
+    fn apply_stop_words_retroactively() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_retained_terms().with_preserved_accents());
+        index.ingest("file1.txt".to_string(), "the café").expect("Unable to ingest data");
+        index.ingest_sections("file2.txt".to_string(), &[("title", "The world")]).expect("Unable to ingest data");
+        let stop_words: HashSet<String> = vec!["The".to_string(), "cafe".to_string()].into_iter().collect();
+        index.apply_stop_words(&stop_words).unwrap();
+        assert_eq!(None, index.search("the").unwrap());
+        assert_eq!(None, index.search("cafe").unwrap());
+        assert_eq!(None, index.search_fields("title:the").unwrap());
+        assert_eq!(vec!["file2.txt"], index.search_fields("title:world").unwrap().unwrap());
+        assert!(index.config().stop_words.contains("the"));
+        assert!(index.bloom_filters["file1.txt"].terms().unwrap().is_empty());
+        assert!(!index.bloom_filters["file2.txt"].contains("the").unwrap());
+        assert!(index.tokenizer_fingerprint_matches());
+    }
+
+    #[test]
+    fn apply_stop_words_requires_retained_terms() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "the world").expect("Unable to ingest data");
+        let stop_words: HashSet<String> = vec!["the".to_string()].into_iter().collect();
+        assert!(matches!(index.apply_stop_words(&stop_words), Err(Error::TermsNotRetained)));
+        assert_eq!(vec!["file1.txt"], index.search("the").unwrap().unwrap());
+        assert!(index.config().stop_words.is_empty());
+    }
+
+    #[test]
+    fn limit_content_size() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_content_bytes(11));
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        assert!(matches!(index.ingest("file2.txt".to_string(), "word1 word2 "), Err(Error::ContentTooLarge { limit: 11 })));
+        assert!(matches!(index.ingest_reader("file2.txt".to_string(), "word1 word2 word3".as_bytes()), Err(Error::ContentTooLarge { limit: 11 })));
+        assert!(matches!(index.ingest_reader("file2.txt".to_string(), "word1 word2é".as_bytes()), Err(Error::ContentTooLarge { limit: 11 })));
+        assert!(matches!(index.ingest_sections("file2.txt".to_string(), &[("title", "word1"), ("body", "word2 ")]), Err(Error::ContentTooLarge { limit: 11 })));
+        assert!(matches!(index.append("file1.txt", "word3 word4 word5"), Err(Error::ContentTooLarge { limit: 11 })));
+        assert_eq!(vec!["file1.txt"], index.bloom_filters.keys().collect::<Vec<&String>>());
+        assert_eq!(None, index.search("word3").unwrap());
+        index.ingest_reader("file2.txt".to_string(), "word3 word4".as_bytes()).expect("Unable to ingest data");
+        assert_eq!(vec!["file2.txt"], index.search("word3").unwrap().unwrap());
+    }
+
+    #[test]
+    fn ingest_and_search_explicit_tokens() {
+        let mut index = Index::new(0.01);
+        index.ingest_tokens("file1.txt".to_string(), vec!["Métro", "C++", "new york", "C++"]).expect("Unable to ingest data");
+        index.ingest_tokens("file2.txt".to_string(), &["metro".to_string()]).expect("Unable to ingest data");
+        let mut expected = BloomFilter::new(3, 0.01);
+        for token in &["Métro", "C++", "new york"] {
+            expected.insert(token).unwrap();
+        }
+        assert_eq!(expected, *index.bloom_filters["file1.txt"]);
+        assert_eq!(vec!["file1.txt"], index.search_terms(vec!["Métro", "new york"]).unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search_terms(vec!["metro"]).unwrap().unwrap());
+        assert_eq!(None, index.search_terms(vec!["C"]).unwrap());
+        assert_eq!(None, index.search_terms(Vec::<String>::new()).unwrap());
+    }
+
+    #[test]
+    fn get_error_rate() {
+        let mut index = Index::new(0.001);
+        assert_eq!(0.001, index.error_rate());
+        index.set_error_rate(0.01).unwrap();
+        assert_eq!(0.01, index.error_rate());
+        let index = Index::restore(&fs::read_to_string("./test/data/test_restore.json").expect("Unable to read dump"));
+        assert_eq!(0.1, index.error_rate());
+        let restored = Index::try_restore(&serde_json::to_string(&Index::new(0.0005)).unwrap()).unwrap();
+        assert_eq!(0.0005, restored.error_rate());
+    }
+
+    #[test]
+    fn change_error_rate_of_new_documents() {
+        let mut index = Index::new(0.1);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.set_error_rate(0.0001).unwrap();
+        index.ingest("file2.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        let old_geometry = BloomFilter::new(2, 0.1);
+        let new_geometry = BloomFilter::new(2, 0.0001);
+        assert_eq!(old_geometry.bitfield_size(), index.bloom_filters["file1.txt"].bitfield_size());
+        assert_eq!(old_geometry.key_size(), index.bloom_filters["file1.txt"].key_size());
+        assert_eq!(new_geometry.bitfield_size(), index.bloom_filters["file2.txt"].bitfield_size());
+        assert_eq!(new_geometry.key_size(), index.bloom_filters["file2.txt"].key_size());
+        for rate in &[0.0, 1.0, -0.5, 2.0, f32::NAN] {
+            assert!(matches!(index.set_error_rate(*rate), Err(Error::InvalidErrorRate)));
+        }
+        assert_eq!(0.0001, index.config().error_rate);
+    }
+
+    #[test]
+    fn ingest_file_in_two_passes() {
+        let path = env::temp_dir().join(format!("index_bloom_two_pass_{}.txt", process::id()));
+        let content: Vec<String> = (0..200).map(|i| format!("word{} common word{}\r\n", i, i / 2)).collect();
+        fs::write(&path, content.concat()).expect("Unable to write file");
+        let mut index = Index::new(0.001);
+        index.ingest_file("in_memory".to_string(), &path).expect("Unable to ingest data");
+        index.ingest_file_two_pass("two_pass".to_string(), &path).expect("Unable to ingest data");
+        let mut limited_index = Index::with_config(IndexConfig::new(0.001).with_max_unique_terms(10));
+        limited_index.ingest_file("in_memory".to_string(), &path).expect("Unable to ingest data");
+        limited_index.ingest_file_two_pass("two_pass".to_string(), &path).expect("Unable to ingest data");
+        fs::remove_file(&path).expect("Unable to remove file");
+        assert_eq!(index.bloom_filters["in_memory"], index.bloom_filters["two_pass"]);
+        assert_eq!(limited_index.bloom_filters["in_memory"], limited_index.bloom_filters["two_pass"]);
+        assert_eq!(vec!["in_memory", "two_pass"], index.search_set("word199 common").unwrap().into_iter().collect::<Vec<&String>>());
+    }
+
+    #[test]
+    fn estimate_unique_words() {
+        let index = Index::new(0.01);
+        assert_eq!(0, index.cardinality_estimate("".as_bytes()).unwrap());
+        assert_eq!(3, index.cardinality_estimate("word1 WORD1\nword2 word3".as_bytes()).unwrap());
+        let content: String = (0..100_000).map(|i| format!("word{} word{}\n", i, i / 2)).collect();
+        let estimate = index.cardinality_estimate(content.as_bytes()).unwrap() as f64;
+        assert!((estimate - 100_000.0).abs() < 3_000.0, "estimate {}", estimate);
+        let index = Index::with_config(IndexConfig::new(0.01).with_max_content_bytes(10));
+        assert!(matches!(index.cardinality_estimate(content.as_bytes()), Err(Error::ContentTooLarge { limit: 10 })));
+    }
+
+    #[test]
+    fn search_with_required_tags() {
+        let mut index = Index::new(0.01);
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<String>>();
+        index.ingest_with_tags("file1.txt".to_string(), "word1 word2", tags(&["lang:rust", "type:doc"])).expect("Unable to ingest data");
+        index.ingest_with_tags("file2.txt".to_string(), "word1 word3", tags(&["lang:rust"])).expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search_tagged("word1", &[]).unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_tagged("word1", &["lang:rust"]).unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_tagged("word1", &["type:doc", "lang:rust"]).unwrap().unwrap());
+        assert_eq!(None, index.search_tagged("word3", &["type:doc"]).unwrap());
+        assert_eq!(None, index.search_tagged("word1", &["Lang:rust"]).unwrap());
+        let restored = Index::restore(&serde_json::to_string(&index).unwrap());
+        assert_eq!(vec!["file1.txt"], restored.search_tagged("word1", &["type:doc"]).unwrap().unwrap());
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["file2.txt"], index.search_tagged("word1", &["lang:rust"]).unwrap().unwrap());
+    }
+
+    #[test]
+    fn split_and_rebuild_from_parts() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word2 word3").expect("Unable to ingest data");
+        let dump_path = env::temp_dir().join(format!("index_bloom_parts_{}.json", process::id()));
+        index.auto_dump_on_drop(&dump_path);
+        let (error_rate, filters) = index.into_parts();
+        assert!(!dump_path.exists());
+        assert_eq!(0.01, error_rate);
+        assert_eq!(2, filters.len());
+        let index = Index::from_parts(error_rate, filters);
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search("word2").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("word3").unwrap().unwrap());
+        assert_eq!(None, index.search("word1 word3").unwrap());
+        assert_eq!(0.01, index.config().error_rate);
+    }
+
+    #[test]
+    fn count_and_replace_empty_words() {
+        let mut index = Index::new(0.01);
+        let stats = index.ingest_with_stats("file1.txt".to_string(), "word1 ☃ ♥♥\nword2").expect("Unable to ingest data");
+        assert_eq!(IngestStats { empty_words: 2, clamped: false }, stats);
+        assert_eq!(BloomFilter::new(2, 0.01).bitfield_size(), index.bloom_filters["file1.txt"].bitfield_size());
+        assert_eq!(None, index.search("☃").unwrap());
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_empty_word_policy(EmptyWordPolicy::Placeholder));
+        let stats = index.ingest_with_stats("file1.txt".to_string(), "word1 ☃ ♥♥\nword2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(IngestStats { empty_words: 2, clamped: false }, stats);
+        assert_eq!(vec!["file1.txt"], index.search("♥").unwrap().unwrap());
+        assert_eq!(IngestStats::default(), index.ingest_with_stats("file3.txt".to_string(), "word1").unwrap());
+    }
+
+    #[test]
+    fn bound_bitfield_size() {
+        let content: String = (0..10_000).map(|i| format!("word{} ", i)).collect();
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_bitfield_bytes(1024));
+        index.ingest("small.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        assert!(matches!(index.ingest("large.txt".to_string(), &content), Err(Error::FilterTooLarge { limit: 1024 })));
+        assert_eq!(1, index.bloom_filters.len());
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_bitfield_bytes(1024).with_clamped_bitfield().with_retained_terms());
+        assert!(!index.ingest_with_stats("small.txt".to_string(), "word1 word2").unwrap().clamped);
+        assert!(index.ingest_with_stats("large.txt".to_string(), &content).unwrap().clamped);
+        let filter = &index.bloom_filters["large.txt"];
+        assert_eq!(1024 * 8, filter.bitfield_size());
+        assert!(filter.retains_terms());
+        assert!(filter.false_positive_rate() > 0.01);
+        assert_eq!(vec!["large.txt"], index.search("word9999").unwrap().unwrap());
+        index.append("large.txt", "extra").expect("Unable to append data");
+        assert_eq!(1024 * 8, index.bloom_filters["large.txt"].bitfield_size());
+        assert_eq!(vec!["large.txt"], index.search("extra word9999").unwrap().unwrap());
+    }
+
+    #[test]
+    fn bound_appended_bitfield_size() {
+        let content: String = (0..10_000).map(|i| format!("word{} ", i)).collect();
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_bitfield_bytes(1024).with_retained_terms());
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        let filter = index.bloom_filters["file1.txt"].clone();
+        assert!(matches!(index.append("file1.txt", &content), Err(Error::FilterTooLarge { limit: 1024 })));
+        assert_eq!(filter, index.bloom_filters["file1.txt"]);
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_bitfield_bytes(1024).with_clamped_bitfield().with_retained_terms());
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.append("file1.txt", &content).expect("Unable to append data");
+        let filter = &index.bloom_filters["file1.txt"];
+        assert_eq!(1024 * 8, filter.bitfield_size());
+        assert!(filter.retains_terms());
+        assert_eq!(vec!["file1.txt"], index.search("word1 word9999").unwrap().unwrap());
+    }
+
+    #[test]
+    fn single_token_search_matches_general_search() {
+        let mut index = Index::new(0.01);
+        for i in 0..200 {
+            let content = format!("word{} word{} common", i, i % 7);
+            let mut config_index = Index::new(0.1 + 0.8 * (i % 3) as f32 / 3.0);
+            config_index.ingest("tmp".to_string(), &content).expect("Unable to ingest data");
+            index.bloom_filters.insert(format!("file{}.txt", i), config_index.bloom_filters.remove("tmp").unwrap());
+        }
+        for word in &["word1", "word3", "word150", "common", "absent", "word1000"] {
+            let tokens = vec![word.to_string()];
+            let mut expected: Vec<&String> = index.bloom_filters.iter()
+                .filter(|(_, filter)| filter.contains_all(&tokens).unwrap())
+                .map(|(name, _)| name)
+                .collect();
+            expected.sort();
+            assert_eq!(expected, index.search(word).unwrap().unwrap_or_default(), "{}", word);
+        }
+        assert_eq!(None, Index::new(0.01).search("word1").unwrap());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored in release mode"]
+    fn single_token_search_is_faster() {
+        use std::time::Instant;
+        let mut index = Index::new(0.0001);
+        for i in 0..2_000 {
+            index.ingest(format!("file{}.txt", i), &format!("word{} common", i)).expect("Unable to ingest data");
+        }
+        let tokens = vec!["common".to_string()];
+        let start = Instant::now();
+        for _ in 0..20 {
+            for filter in index.bloom_filters.values() {
+                filter.contains_all(&tokens).unwrap();
+            }
+        }
+        let general_duration = start.elapsed();
+        let start = Instant::now();
+        for _ in 0..20 {
+            index.search_tokens(&tokens).unwrap();
+        }
+        let single_duration = start.elapsed();
+        assert!(single_duration < general_duration, "single token {:?}, general {:?}", single_duration, general_duration);
+    }
+
+    #[test]
+    fn collapse_aliased_documents() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_normalized_names());
+        index.ingest("docs/target.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("link.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("other.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["docs/target.txt", "link.txt", "other.txt"], index.search("word1").unwrap().unwrap());
+        index.register_alias("./link.txt".to_string(), "docs//target.txt".to_string());
+        assert_eq!(vec!["docs/target.txt", "other.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(vec!["docs/target.txt"], index.search("word2").unwrap().unwrap());
+        assert_eq!(vec!["docs/target.txt", "other.txt"], index.search_query("word1 OR word2").unwrap().unwrap());
+        assert_eq!(vec!["docs/target.txt", "other.txt"], index.search_with_stats("word1").unwrap().0.unwrap());
+        assert_eq!(2, index.search_with_stats("word1").unwrap().1.matches);
+        assert_eq!(vec!["docs/target.txt", "other.txt"], index.search_expanded("word1").unwrap().unwrap());
+        assert_eq!(vec!["docs/target.txt", "other.txt"], index.search_fields("word1").unwrap().unwrap());
+        let restored = Index::restore(&serde_json::to_string(&index).unwrap());
+        assert_eq!(vec!["docs/target.txt", "other.txt"], restored.search("word1").unwrap().unwrap());
+    }
+
+    #[test]
+    fn hash_error_policies() {
+        use crate::bloom_filter::blake2b_digest;
+        fn set_digest(index: &mut Index, digest: fn(&[u8]) -> Vec<u8>) {
+            for filter in index.bloom_filters.values_mut() {
+                *filter = Arc::new(filter.as_ref().clone().with_digest(digest));
+            }
+        }
+        // The digest of "word2" repeated any number of times is empty, so that it cannot be hashed
+        let unhashable_word2: fn(&[u8]) -> Vec<u8> = |data| if data.chunks(5).all(|chunk| chunk == b"word2") { Vec::new() } else { blake2b_digest(data) };
+        let mut closed_index = Index::new(0.01);
+        let mut open_index = Index::with_config(IndexConfig::new(0.01).with_hash_error_policy(HashErrorPolicy::FailOpen));
+        for index in [&mut closed_index, &mut open_index] {
+            index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+            index.ingest("file2.txt".to_string(), "word1").expect("Unable to ingest data");
+        }
+        set_digest(&mut closed_index, unhashable_word2);
+        set_digest(&mut open_index, unhashable_word2);
+        assert!(matches!(closed_index.search("word2"), Err(Error::HashWord(_))));
+        assert!(matches!(closed_index.search("word1 word2"), Err(Error::HashWord(_))));
+        assert!(matches!(closed_index.search_ranked("word1 word2"), Err(Error::HashWord(_))));
+        assert_eq!(None, open_index.search("word2").unwrap());
+        assert_eq!(None, open_index.search("word1 word2").unwrap());
+        assert_eq!(vec![(&"file1.txt".to_string(), 1), (&"file2.txt".to_string(), 1)], open_index.search_ranked("word1 word2").unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], open_index.search("word1").unwrap().unwrap());
+        assert!(matches!(closed_index.search_with_stats("word1 word2"), Err(Error::HashWord(_))));
+        assert!(matches!(closed_index.search_query("word2 OR word1"), Err(Error::HashWord(_))));
+        assert!(matches!(closed_index.term_exists("word2"), Err(Error::HashWord(_))));
+        assert_eq!(None, open_index.search_with_stats("word1 word2").unwrap().0);
+        assert_eq!(vec!["file1.txt", "file2.txt"], open_index.search_query("word2 OR word1").unwrap().unwrap());
+        assert_eq!(None, open_index.search_query("word1 AND word2").unwrap());
+        assert!(!open_index.term_exists("word2").unwrap());
+        assert!(!open_index.has_any_match("word1 word2").unwrap());
+        assert_eq!(None, open_index.search_expanded("word1 word2").unwrap());
+        assert_eq!(vec![(&"file1.txt".to_string(), 1.0), (&"file2.txt".to_string(), 1.0)], open_index.search_by_rarity("word1 word2").unwrap());
+        set_digest(&mut closed_index, blake2b_digest);
+        assert_eq!(vec!["file1.txt"], closed_index.search("word1 word2").unwrap().unwrap());
+    }
+
+    #[test]
+    fn ingest_json_fields() {
+        let mut index = Index::new(0.01);
+        let json = r#"{"title": "Rust book", "body": "All about ownership", "meta": {"author": "Steve", "tags": ["chapter"]}, "pages": 512}"#;
+        index.ingest_json("book.json".to_string(), json, &["title", "meta.author", "meta.tags", "pages", "missing.field"]).expect("Unable to ingest data");
+        assert_eq!(vec!["book.json"], index.search_fields("title:rust").unwrap().unwrap());
+        assert_eq!(vec!["book.json"], index.search_fields("meta.author:steve").unwrap().unwrap());
+        assert_eq!(None, index.search_fields("meta.author:rust").unwrap());
+        assert_eq!(None, index.search("ownership").unwrap());
+        assert_eq!(None, index.search("chapter").unwrap());
+        assert_eq!(None, index.search("512").unwrap());
+        assert!(matches!(index.ingest_json("invalid.json".to_string(), "{\"title\":", &["title"]), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn warm_up_restored_index() {
+        let index = Index::restore(&fs::read_to_string("./test/data/test_restore.json").expect("Unable to read dump"));
+        assert!(index.warm_up().is_ok());
+        let corrupted_dump = "{\"error_rate\":0.1,\"bloom_filters\":{\"file1.txt\":{\"key_size\":4,\"bitfield\":[8,130,255],\"bitfield_size\":20}}}";
+        let index = Index::restore(corrupted_dump);
+        assert!(matches!(index.warm_up(), Err(Error::CorruptedFilter)));
+        let corrupted_dump = "{\"error_rate\":0.1,\"bloom_filters\":{\"file1.txt\":{\"key_size\":0,\"bitfield\":[8,130,65],\"bitfield_size\":24}}}";
+        assert!(matches!(Index::restore(corrupted_dump).warm_up(), Err(Error::CorruptedFilter)));
+    }
+
+    #[test]
+    fn filters_independent_of_insertion_order() {
+        let lines: Vec<String> = (0..50).map(|i| format!("word{} common word{}", i, i % 5)).collect();
+        let mut reference = Index::new(0.01);
+        reference.ingest("file.txt".to_string(), &lines.join("\n")).expect("Unable to ingest data");
+        let reference_dump = serde_json::to_string(&reference).unwrap();
+        for shift in [0, 1, 17, 49] {
+            let mut shuffled_lines = lines.clone();
+            shuffled_lines.rotate_left(shift);
+            shuffled_lines.reverse();
+            let mut index = Index::new(0.01);
+            index.ingest("file.txt".to_string(), &shuffled_lines.join("\n")).expect("Unable to ingest data");
+            assert_eq!(reference.bloom_filters["file.txt"], index.bloom_filters["file.txt"]);
+            assert_eq!(reference_dump, serde_json::to_string(&index).unwrap());
+        }
+    }
+
+    #[test]
+    fn share_filters_of_identical_documents() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_shared_filters().with_retained_terms());
+        let mut unshared_index = Index::with_config(IndexConfig::new(0.01).with_retained_terms());
+        for name in &["file1.txt", "file2.txt", "file3.txt"] {
+            index.ingest(name.to_string(), "word1 word2").expect("Unable to ingest data");
+            unshared_index.ingest(name.to_string(), "word1 word2").expect("Unable to ingest data");
+        }
+        assert!(index.memory_usage() < unshared_index.memory_usage());
+        index.ingest("file4.txt".to_string(), "word3").expect("Unable to ingest data");
+        assert_eq!(3, Arc::strong_count(&index.bloom_filters["file1.txt"]));
+        assert!(Arc::ptr_eq(&index.bloom_filters["file1.txt"], &index.bloom_filters["file3.txt"]));
+        assert!(!Arc::ptr_eq(&index.bloom_filters["file1.txt"], &index.bloom_filters["file4.txt"]));
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search("word1").unwrap().unwrap());
+        index.append("file2.txt", "word4").expect("Unable to append data");
+        assert_eq!(vec!["file2.txt"], index.search("word4").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search("word1").unwrap().unwrap());
+        index.ingest("file5.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        assert_eq!(None, index.search("word4 word3").unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("word4").unwrap().unwrap());
+    }
+
+    #[test]
+    fn ingest_from_reader() {
+        let mut index = Index::new(0.01);
+        index.ingest_reader("file1.txt".to_string(), "word1 word2\nword3".as_bytes()).expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word1 word3").unwrap().unwrap());
+        let invalid_utf8: &[u8] = &[0x77, 0xff, 0xfe];
+        assert!(matches!(index.ingest_reader("file2.txt".to_string(), invalid_utf8), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn ingest_from_file() {
+        let path = env::temp_dir().join(format!("index_bloom_ingest_{}.txt", process::id()));
+        fs::write(&path, "word1 word2\nword3").expect("Unable to write test file");
+        let mut index = Index::new(0.01);
+        index.ingest_file("file1.txt".to_string(), &path).expect("Unable to ingest data");
+        fs::remove_file(&path).expect("Unable to remove test file");
+        assert_eq!(vec!["file1.txt"], index.search("word1 word3").unwrap().unwrap());
+        assert!(matches!(index.ingest_file("file2.txt".to_string(), &path), Err(Error::Io(_))));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn ingest_from_gzip_file() {
+        use std::io::Write;
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let content = "word1 word2\nword3";
+        let path = env::temp_dir().join(format!("index_bloom_ingest_{}.txt.gz", process::id()));
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes()).expect("Unable to compress test content");
+        fs::write(&path, encoder.finish().expect("Unable to compress test content")).expect("Unable to write test file");
+        let mut index = Index::new(0.01);
+        index.ingest_file("compressed.txt".to_string(), &path).expect("Unable to ingest data");
+        index.ingest("plain.txt".to_string(), content).expect("Unable to ingest data");
+        fs::remove_file(&path).expect("Unable to remove test file");
+        assert_eq!(index.bloom_filters["plain.txt"], index.bloom_filters["compressed.txt"]);
+        assert_eq!(vec!["compressed.txt", "plain.txt"], index.search("word1 word3").unwrap().unwrap());
+    }
+
+    #[test]
+    fn search_as_set() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word2 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word4").expect("Unable to ingest data");
+        let word2_documents = index.search_set("word2").unwrap();
+        assert_eq!(index.search("word2").unwrap().unwrap(), word2_documents.iter().copied().collect::<Vec<&String>>());
+        let word3_documents = index.search_set("word3").unwrap();
+        assert_eq!(vec!["file2.txt"], word2_documents.intersection(&word3_documents).copied().collect::<Vec<&String>>());
+        assert!(index.search_set("word5").unwrap().is_empty());
+    }
+
+    #[test]
+    fn limit_query_hashing_budget() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_query_bytes(10).with_stop_words(vec!["the"]));
+        index.ingest_sections("file1.txt".to_string(), &[("title", "word1 word2")]).expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("the word1 word2 word1 the").unwrap().unwrap());
+        assert!(matches!(index.search("word1 word2 word3"), Err(Error::QueryBudgetExceeded { limit: 10 })));
+        assert!(matches!(index.search_ranked("word1 word2 word3"), Err(Error::QueryBudgetExceeded { limit: 10 })));
+        assert!(matches!(index.search_fields("title:word1 word2 word3"), Err(Error::QueryBudgetExceeded { limit: 10 })));
+        assert!(matches!(index.term_exists(&"a".repeat(11)), Err(Error::QueryBudgetExceeded { limit: 10 })));
+        assert!(!index.term_exists("the").unwrap());
+    }
+
+    #[test]
+    fn diff_search_between_snapshots() {
+        let mut old = Index::new(0.01);
+        old.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        old.ingest("file2.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        old.ingest("file3.txt".to_string(), "word2").expect("Unable to ingest data");
+        let mut new = Index::new(0.01);
+        new.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        new.ingest("file2.txt".to_string(), "word1").expect("Unable to ingest data");
+        new.ingest("file3.txt".to_string(), "word2").expect("Unable to ingest data");
+        new.ingest("file4.txt".to_string(), "word2").expect("Unable to ingest data");
+        let (newly_matching, no_longer_matching) = diff_search(&old, &new, "word2").unwrap();
+        assert_eq!(vec!["file1.txt", "file4.txt"], newly_matching);
+        assert_eq!(vec!["file2.txt"], no_longer_matching);
+        let (newly_matching, no_longer_matching) = diff_search(&old, &old, "word2").unwrap();
+        assert!(newly_matching.is_empty() && no_longer_matching.is_empty());
+    }
+
+    #[test]
+    fn search_boolean_queries() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_stop_words(vec!["the"]));
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word2 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search_query("word1 AND word2").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_query("word1 word2").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search_query("word1 OR word2").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt", "file3.txt"], index.search_query("(word1 OR word2) AND word3").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_query("word2 OR (the AND word4)").unwrap().unwrap());
+        assert_eq!(None, index.search_query("word4 OR word5").unwrap());
+        assert_eq!(None, index.search_query("the").unwrap());
+        assert!(matches!(index.search_query("(word1 OR word2"), Err(Error::InvalidQuery)));
+    }
+
+    #[test]
+    fn search_with_predicate() {
+        let mut index = Index::new(0.01);
+        for position in 0..6 {
+            index.ingest(format!("file{}.txt", position), "word1").expect("Unable to ingest data");
+        }
+        let even = |name: &str| name.trim_start_matches("file").trim_end_matches(".txt").parse::<usize>().unwrap() % 2 == 0;
+        assert_eq!(vec!["file0.txt", "file2.txt", "file4.txt"], index.search_filtered("word1", even).unwrap().unwrap());
+        assert_eq!(None, index.search_filtered("word1", |_| false).unwrap());
+        assert_eq!(None, index.search_filtered("word2", |_| true).unwrap());
+    }
+
+    #[test]
+    fn search_raw_bytes() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 café").expect("Unable to ingest data");
+        assert_eq!(index.search("word1 café").unwrap(), index.search_bytes("word1 café".as_bytes()).unwrap());
+        assert_eq!(None, index.search_bytes(b"word2").unwrap());
+        assert!(matches!(index.search_bytes(&[0x77, 0xff, 0xfe]), Err(Error::Utf8(_))));
+    }
+
+    #[test]
+    fn search_with_synonyms() {
+        let config = IndexConfig::new(0.01).with_synonyms("car", vec!["automobile", "vehicle"]);
+        let mut index = Index::with_config(config);
+        index.ingest("file1.txt".to_string(), "red automobile").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "blue car").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "red bicycle").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_expanded("car").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_expanded("red car").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("car").unwrap().unwrap());
+        assert_eq!(None, index.search_expanded("automobile blue").unwrap());
+        assert_eq!(None, index.search_expanded("").unwrap());
+    }
+
+    #[test]
+    fn search_field_qualified_keywords() {
+        let mut index = Index::new(0.01);
+        index.ingest_sections("file1.txt".to_string(), &[("title", "Rust"), ("body", "async programming")]).expect("Unable to ingest data");
+        index.ingest_sections("file2.txt".to_string(), &[("Title", "Programming"), ("body", "rust async")]).expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "rust async").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search_fields("title:rust").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search_fields("TITLE:programming body:Rust").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_fields("body:async").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search_fields("rust async").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], index.search("rust async").unwrap().unwrap());
+        assert_eq!(None, index.search_fields("title:async").unwrap());
+        assert_eq!(None, index.search_fields("author:rust").unwrap());
+    }
+
+    #[test]
+    fn combine_field_scores() {
+        let mut index = Index::new(0.0001);
+        index.ingest_sections("file1.txt".to_string(), &[("title", "rust async")]).expect("Unable to ingest data");
+        index.ingest_sections("file2.txt".to_string(), &[("title", "rust"), ("summary", "rust"), ("body", "rust")]).expect("Unable to ingest data");
+        assert_eq!(vec![(&"file1.txt".to_string(), 2.0), (&"file2.txt".to_string(), 1.0)], index.search_fields_ranked("rust async").unwrap());
+        index.config.field_combiner = FieldCombiner::Sum;
+        assert_eq!(vec![(&"file2.txt".to_string(), 3.0), (&"file1.txt".to_string(), 2.0)], index.search_fields_ranked("rust async").unwrap());
+        assert_eq!(vec![(&"file1.txt".to_string(), 1.0), (&"file2.txt".to_string(), 1.0)], index.search_fields_ranked("title:rust").unwrap());
+        let weights = vec![("body".to_string(), 0.0), ("summary".to_string(), 0.5)].into_iter().collect();
+        index.config.field_combiner = FieldCombiner::WeightedSum(weights);
+        assert_eq!(vec![(&"file1.txt".to_string(), 2.0), (&"file2.txt".to_string(), 1.5)], index.search_fields_ranked("rust async").unwrap());
+    }
+
+    #[test]
+    fn replace_sectioned_document() {
+        let mut index = Index::new(0.01);
+        index.ingest_sections("file1.txt".to_string(), &[("title", "Rust"), ("body", "async")]).expect("Unable to ingest data");
+        index.ingest("file1.txt".to_string(), "rust async").expect("Unable to ingest data");
+        assert_eq!(None, index.search_fields("title:rust").unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_fields("rust").unwrap().unwrap());
+    }
+
+    #[test]
+    fn search_with_stats_scans_every_filter() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2\nword3").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word4").expect("Unable to ingest data");
+        let (hits, stats) = index.search_with_stats("word1 word3").unwrap();
+        assert_eq!(index.search("word1 word3").unwrap(), hits);
+        assert_eq!(3, stats.filters_scanned);
+        assert_eq!(2, stats.matches);
+        assert!(stats.probes >= 3);
+        let (hits, stats) = index.search_with_stats("").unwrap();
+        assert_eq!(None, hits);
+        assert_eq!(SearchStats::default(), stats);
+    }
+
+    #[test]
+    fn any_match_stops_at_first_match() {
+        let mut index = Index::new(0.01);
+        for position in 0..10 {
+            index.ingest(format!("file{}.txt", position), "word1 word2").expect("Unable to ingest data");
+        }
+        index.ingest("file10.txt".to_string(), "word3").expect("Unable to ingest data");
+        let mut stats = SearchStats::default();
+        assert!(index.has_any_match_with_stats("word1 word2", &mut stats).unwrap());
+        assert!(stats.filters_scanned < 3);
+        assert_eq!(1, stats.matches);
+        let mut stats = SearchStats::default();
+        assert!(!index.has_any_match_with_stats("word4", &mut stats).unwrap());
+        assert_eq!(11, stats.filters_scanned);
+        assert_eq!(0, stats.matches);
+        assert!(index.has_any_match("word3").unwrap());
+        assert!(!Index::new(0.01).has_any_match("word1").unwrap());
+    }
+
+    #[test]
+    fn edit_distance_one() {
+        let edits = single_edits("ab");
+        for edit in &["a", "b", "ba", "xab", "axb", "abx", "xb", "ax"] {
+            assert!(edits.contains(*edit), "{} should be an edit of ab", edit);
+        }
+        assert!(!edits.contains("ab"));
+        assert!(!edits.contains("abxy"));
+        // 2 deletions, 1 swap, 3 * 36 insertions of which "aab" and "abb" twice, 2 * 35 replacements
+        assert_eq!(2 + 1 + 3 * 36 - 2 + 2 * 35, edits.len());
+    }
+
+    #[test]
+    fn search_corrected_with_dictionary() {
+        let mut index = Index::new(0.00001);
+        index.ingest("file1.txt".to_string(), "bloom filter").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "bloom index").expect("Unable to ingest data");
+        let dictionary: HashSet<String> = ["Bloom", "filter", "index"].iter().map(|word| word.to_string()).collect();
+        assert_eq!(None, index.search("blom fliter").unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_with_dictionary("blom fliter", &dictionary).unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search_with_dictionary("bloom idex", &dictionary).unwrap().unwrap());
+        assert_eq!(None, index.search_with_dictionary("bloom fltr", &dictionary).unwrap());
+        assert_eq!(None, index.search_with_dictionary("blom fliter", &HashSet::new()).unwrap());
+    }
+
+    #[test]
+    fn suggest_corrections() {
+        let mut index = Index::new(0.00001);
+        index.ingest("file1.txt".to_string(), "bloom filter").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "bloom index").expect("Unable to ingest data");
+        assert_eq!(vec!["bloom"], index.suggest("blom", 5).unwrap());
+        let suggestions = index.suggest("bloom fliter", 5).unwrap();
+        assert_eq!(vec!["filter"], suggestions);
+        assert_eq!(vec!["file1.txt"], index.search(&format!("bloom {}", suggestions[0])).unwrap().unwrap());
+        assert_eq!(vec!["bloom"], index.suggest("blom idex", 1).unwrap());
+        assert!(index.suggest("bloom filter", 5).unwrap().is_empty());
+        assert!(index.suggest("zzzzzz", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn iterate_documents_with_stats() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word4 word5 word6").expect("Unable to ingest data");
+        let mut names = Vec::new();
+        for (name, stats) in &index {
+            let filter = &index.bloom_filters[name];
+            assert_eq!(filter.key_size(), stats.key_size);
+            assert_eq!(filter.bitfield_size(), stats.bitfield_size);
+            assert!(stats.fill_ratio > 0.0);
+            assert_eq!(filter.estimated_count(), stats.estimated_count);
+            names.push(name);
+        }
+        names.sort();
+        assert_eq!(vec!["file1.txt", "file2.txt", "file3.txt"], names);
+        assert_eq!(3, index.iter().count());
+    }
+
+    #[test]
+    fn compact_after_churn() {
+        let mut index = Index::new(0.01);
+        for cycle in 0..10 {
+            for doc in 0..100 {
+                index.ingest(format!("file{}.txt", doc), &format!("word{} cycle{}", doc, cycle)).expect("Unable to ingest data");
+            }
+        }
+        for doc in 10..100 {
+            let name = format!("file{}.txt", doc);
+            index.bloom_filters.remove(&name);
+            index.insertion_order.remove(&name);
+        }
+        let expected_hits: Vec<Option<Vec<String>>> = (0..100)
+            .map(|doc| index.search(&format!("word{}", doc)).unwrap().map(|hits| hits.into_iter().cloned().collect()))
+            .collect();
+        let usage_before = index.memory_usage();
+        index.compact();
+        assert!(index.memory_usage() < usage_before);
+        for (doc, expected) in expected_hits.iter().enumerate() {
+            let hits = index.search(&format!("word{}", doc)).unwrap().map(|hits| hits.into_iter().cloned().collect());
+            assert_eq!(expected, &hits);
+        }
+        assert_eq!(vec!["file3.txt"], index.search("word3 cycle9").unwrap().unwrap());
+    }
+
+    #[test]
+    fn normalize_document_names() {
+        assert_eq!("a.txt", normalize_name("./a.txt"));
+        assert_eq!("docs/a", normalize_name("docs//a/"));
+        assert_eq!("docs/a", normalize_name("./docs/./a"));
+        assert_eq!("docs/a", normalize_name("docs\\a"));
+        assert_eq!("/docs/a", normalize_name("/docs/a"));
+        assert_eq!("../docs/a", normalize_name("../docs/a"));
+    }
+
+    #[test]
+    fn ingest_with_normalized_names() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_normalized_names().with_retained_terms());
+        index.ingest("./a.txt".to_string(), "word1").expect("Unable to ingest data");
+        index.ingest("a.txt".to_string(), "word2").expect("Unable to ingest data");
+        index.append(".//a.txt", "word3").expect("Unable to append data");
+        assert_eq!(1, index.bloom_filters.len());
+        assert_eq!(None, index.search("word1").unwrap());
+        assert_eq!(vec!["a.txt"], index.search("word2 word3").unwrap().unwrap());
+        assert_eq!(Some(&"a.txt".to_string()), index.find_document_ci("./A.TXT"));
+        let mut index = Index::new(0.01);
+        index.ingest("./a.txt".to_string(), "word1").expect("Unable to ingest data");
+        index.ingest("a.txt".to_string(), "word1").expect("Unable to ingest data");
+        assert_eq!(vec!["./a.txt", "a.txt"], index.search("word1").unwrap().unwrap());
+    }
+
+    #[test]
+    fn name_prefixes() {
+        assert_eq!("logs", name_prefix("logs/2020/a.txt", 1));
+        assert_eq!("logs/2020", name_prefix("logs/2020/a.txt", 2));
+        assert_eq!("logs/2020/a.txt", name_prefix("logs/2020/a.txt", 3));
+        assert_eq!("a.txt", name_prefix("a.txt", 1));
+        assert_eq!("", name_prefix("logs/a.txt", 0));
+    }
+
+    #[test]
+    fn diverse_search_caps_results_per_prefix() {
+        let mut index = Index::new(0.01);
+        for position in 0..10 {
+            index.ingest(format!("logs/{}.txt", position), "error").expect("Unable to ingest data");
+        }
+        index.ingest("docs/errors.txt".to_string(), "error").expect("Unable to ingest data");
+        index.ingest("docs/faq.txt".to_string(), "error").expect("Unable to ingest data");
+        index.ingest("readme.txt".to_string(), "error").expect("Unable to ingest data");
+        let expected = vec!["docs/errors.txt", "docs/faq.txt", "logs/0.txt", "logs/1.txt", "readme.txt"];
+        assert_eq!(expected, index.search_diverse("error", 2, 1).unwrap().unwrap());
+        assert_eq!(13, index.search_diverse("error", 2, 2).unwrap().unwrap().len());
+        assert_eq!(None, index.search_diverse("error", 0, 1).unwrap());
+        assert_eq!(None, index.search_diverse("warning", 2, 1).unwrap());
+    }
+
+    #[test]
+    fn find_document_ignoring_case() {
+        let mut index = Index::new(0.01);
+        index.ingest("Doc.TXT".to_string(), "word1").expect("Unable to ingest data");
+        index.ingest("other.txt".to_string(), "word2").expect("Unable to ingest data");
+        assert_eq!(Some(&"Doc.TXT".to_string()), index.find_document_ci("doc.txt"));
+        assert_eq!(Some(&"Doc.TXT".to_string()), index.find_document_ci("Doc.TXT"));
+        assert_eq!(None, index.find_document_ci("doc.md"));
+    }
+
+    #[test]
+    fn complete_document_names() {
+        let mut index = Index::new(0.01);
+        for name in &["docs/b.md", "docs/a.md", "doc.txt", "src/docs.rs", "Docs/c.md"] {
+            index.ingest(name.to_string(), "word1").expect("Unable to ingest data");
+        }
+        assert_eq!(vec!["doc.txt", "docs/a.md", "docs/b.md"], index.complete_name("doc", 10));
+        assert_eq!(vec!["doc.txt", "docs/a.md"], index.complete_name("doc", 2));
+        assert_eq!(vec!["docs/a.md", "docs/b.md"], index.complete_name("docs/", 10));
+        assert_eq!(5, index.complete_name("", 10).len());
+        assert!(index.complete_name("doc", 0).is_empty());
+        assert!(index.complete_name("lib", 10).is_empty());
+    }
+
+    #[test]
+    fn ranked_search_by_match_count() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word2 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word4").expect("Unable to ingest data");
+        let hits = index.search_ranked("word1 word2 word3").unwrap();
+        assert_eq!(vec![(&"file2.txt".to_string(), 3), (&"file1.txt".to_string(), 1)], hits);
+        assert!(index.search_ranked("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_grouped_by_match_count() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word2 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word2 word3").expect("Unable to ingest data");
+        index.ingest("file4.txt".to_string(), "word3 word1").expect("Unable to ingest data");
+        index.ingest("file5.txt".to_string(), "word4").expect("Unable to ingest data");
+        let buckets = index.search_buckets("word1 word2 word3").unwrap();
+        assert_eq!(3, buckets.len());
+        assert_eq!(vec!["file1.txt"], buckets[&1]);
+        assert_eq!(vec!["file3.txt", "file4.txt"], buckets[&2]);
+        assert_eq!(vec!["file2.txt"], buckets[&3]);
+        assert!(!buckets.contains_key(&0));
+        assert!(index.search_buckets("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rank_by_cached_rarity() {
+        let uncached_ranking = |index: &Index, keywords: &str| {
+            let tokens = index.query_tokens(keywords).unwrap();
+            let documents_count = index.bloom_filters.len() as f64;
+            let mut result: Vec<(String, f64)> = index.bloom_filters.iter()
+                .map(|(name, filter)| {
+                    let score: f64 = tokens.iter()
+                        .filter(|token| filter.contains(token).unwrap())
+                        .map(|token| 1.0 + ((1.0 + documents_count) / (1.0 + index.document_frequency(token).unwrap() as f64)).ln())
+                        .sum();
+                    (name.clone(), score)
+                })
+                .filter(|(_, score)| *score > 0.0)
+                .collect();
+            result.sort_by(|(name_a, score_a), (name_b, score_b)| score_b.partial_cmp(score_a).unwrap().then_with(|| name_a.cmp(name_b)));
+            result
+        };
+        let cached_ranking = |index: &Index, keywords: &str| {
+            index.search_by_rarity(keywords).unwrap().into_iter().map(|(name, score)| (name.clone(), score)).collect::<Vec<(String, f64)>>()
+        };
+        let mut index = Index::new(0.0001);
+        index.ingest("file1.txt".to_string(), "common rare").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "common").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "common other").expect("Unable to ingest data");
+        let ranking = cached_ranking(&index, "common rare other");
+        assert_eq!(uncached_ranking(&index, "common rare other"), ranking);
+        assert_eq!(vec!["file1.txt", "file3.txt", "file2.txt"], ranking.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>());
+        assert_eq!(Some(&1), index.cached_document_frequencies().get("rare"));
+        index.ingest("file4.txt".to_string(), "rare other").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "rare").expect("Unable to ingest data");
+        assert_eq!(Some(&3), index.cached_document_frequencies().get("rare"));
+        assert_eq!(Some(&2), index.cached_document_frequencies().get("common"));
+        assert_eq!(uncached_ranking(&index, "common rare other"), cached_ranking(&index, "common rare other"));
+    }
+
+    #[test]
+    fn ranked_search_secondary_sort_by_name() {
+        let mut index = Index::new(0.01);
+        index.ingest("file2.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file1.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        let hits = index.search_ranked_by("word1", SecondarySort::Name).unwrap();
+        assert_eq!(vec![(&"file1.txt".to_string(), 1), (&"file2.txt".to_string(), 1)], hits);
+        assert_eq!(hits, index.search_ranked("word1").unwrap());
+    }
+
+    #[test]
+    fn ranked_search_secondary_sort_by_false_positive_rate() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        // Saturate the first document so that its matches are less trustworthy
+        for token in 0..100 {
+            Arc::make_mut(index.bloom_filters.get_mut("file1.txt").unwrap()).insert(&format!("noise{}", token)).unwrap();
+        }
+        assert!(index.bloom_filters["file1.txt"].false_positive_rate() > index.bloom_filters["file2.txt"].false_positive_rate());
+        let hits = index.search_ranked_by("word1", SecondarySort::FalsePositiveRate).unwrap();
+        assert_eq!(vec![(&"file2.txt".to_string(), 1), (&"file1.txt".to_string(), 1)], hits);
+    }
+
+    #[test]
+    fn ranked_search_secondary_sort_by_insertion_order() {
+        let mut index = Index::new(0.01);
+        index.ingest("file2.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("file1.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        let hits = index.search_ranked_by("word1", SecondarySort::InsertionOrder).unwrap();
+        assert_eq!(vec![(&"file2.txt".to_string(), 1), (&"file1.txt".to_string(), 1)], hits);
+        index.ingest("file2.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        let hits = index.search_ranked_by("word1", SecondarySort::InsertionOrder).unwrap();
+        assert_eq!(vec![(&"file1.txt".to_string(), 1), (&"file2.txt".to_string(), 1)], hits);
+    }
+
+    #[test]
+    fn ignore_stop_words_and_token_length() {
+        let config = IndexConfig::new(0.01).with_stop_words(vec!["the"]).with_min_token_length(2).with_max_token_length(6);
+        let mut index = Index::with_config(config);
+        index.ingest("file1.txt".to_string(), "the word1 a w extraordinary").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word1").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("The word1").unwrap().unwrap());
+        assert_eq!(None, index.search("the").unwrap());
+        assert_eq!(None, index.search("w").unwrap());
+        assert_eq!(None, index.search("extraordinary").unwrap());
+        let mut expected = BloomFilter::new(1, 0.01);
+        expected.insert("word1").unwrap();
+        assert_eq!(expected, *index.bloom_filters["file1.txt"]);
+    }
+
+    #[test]
+    fn search_ignored_words_when_alone() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "the word1 a").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1").expect("Unable to ingest data");
+        index.config = IndexConfig::new(0.01).with_stop_words(vec!["the", "a"]);
+        assert_eq!(vec!["file1.txt"], index.search("the").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("The a").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search("the word1").unwrap().unwrap());
+        assert_eq!(vec![(&"file1.txt".to_string(), 1)], index.search_ranked("the").unwrap());
+        assert_eq!(None, index.search("").unwrap());
+    }
+
+    #[test]
+    fn strip_invisible_characters() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_stripped_invisible_characters().with_preserved_accents());
+        index.ingest("file1.txt".to_string(), "word1\u{200B}word2 cafe\u{0301}").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "wo\u{200D}rd3").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("word1 word2 cafe").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_strict("cafe\u{0301}").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("word3").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("w\u{200C}ord3").unwrap().unwrap());
+        let mut raw_index = Index::new(0.01);
+        raw_index.ingest("file1.txt".to_string(), "word1\u{200B}word2").expect("Unable to ingest data");
+        assert_eq!(None, raw_index.search("word1").unwrap());
+    }
+
+    #[test]
+    fn custom_analyzer() {
+        use crate::analyzer::{Length, Lowercase, StopWords, Stemmer};
+
+        let mut index = Index::new(0.01);
+        index.set_analyzer(Analyzer::new()
+            .with_filter(Lowercase)
+            .with_filter(StopWords::new(vec!["the"]))
+            .with_filter(Stemmer)
+            .with_filter(Length::new(3, 10)));
+        index.ingest("file1.txt".to_string(), "The apples (fell) quickly").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("APPLE quick").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("(fell)").unwrap().unwrap());
+        assert_eq!(None, index.search("fell").unwrap());
+        assert_eq!(None, index.search("the").unwrap());
+    }
+
+    #[test]
+    fn search_number_buckets() {
+        use crate::analyzer::{Lowercase, NumberBuckets};
+
+        let mut index = Index::new(0.001);
+        index.set_analyzer(Analyzer::new()
+            .with_filter(Lowercase)
+            .with_filter(NumberBuckets::new("year:", 10)));
+        index.ingest("file1.txt".to_string(), "Released in 2024").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "Released in 2019").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("year:2020s").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("2024").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("year:2010s released").unwrap().unwrap());
+        assert_eq!(None, index.search("year:2000s").unwrap());
+    }
+
+    #[test]
+    fn cap_unique_terms() {
+        let content = (0..10000).map(|word| format!("word{}", word)).collect::<Vec<String>>().join(" ");
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_max_unique_terms(1000));
+        index.ingest("file1.txt".to_string(), &content).expect("Unable to ingest data");
+        let expected = BloomFilter::new(1000, 0.01);
+        let filter = &index.bloom_filters["file1.txt"];
+        assert_eq!(expected.key_size(), filter.key_size());
+        assert_eq!(expected.bitfield_size(), filter.bitfield_size());
+        assert_eq!(vec!["file1.txt"], index.search("word0 word999").unwrap().unwrap());
+    }
+
+    #[test]
+    fn lenient_search_ignores_accents() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_preserved_accents());
+        index.ingest("file1.txt".to_string(), "un café").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "une cafe").expect("Unable to ingest data");
+        let expected = vec!["file1.txt", "file2.txt"];
+        assert_eq!(expected, index.search("cafe").unwrap().unwrap());
+        assert_eq!(expected, index.search("café").unwrap().unwrap());
+    }
+
+    #[test]
+    fn strict_search_distinguishes_accents() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_preserved_accents());
+        index.ingest("file1.txt".to_string(), "un café").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "une cafe").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search_strict("Café").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search_strict("cafe").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_strict("un café").unwrap().unwrap());
+        assert_eq!(None, index.search_strict("une café").unwrap());
+    }
+
+    #[test]
+    fn strict_search_with_turkish_locale() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_preserved_accents());
+        index.ingest("file1.txt".to_string(), "İSTANBUL ILIK").expect("Unable to ingest data");
+        assert_eq!(None, index.search_strict("istanbul").unwrap());
+        assert_eq!(None, index.search_strict("ılık").unwrap());
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_preserved_accents().with_locale(Locale::Turkish));
+        index.ingest("file1.txt".to_string(), "İSTANBUL ILIK").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search_strict("istanbul ılık").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_strict("İstanbul Ilık").unwrap().unwrap());
+        assert_eq!(None, index.search_strict("ilik").unwrap());
+        assert_eq!(vec!["file1.txt"], index.search("istanbul ilik").unwrap().unwrap());
+    }
+
+    #[test]
+    fn strict_search_requires_preserved_accents() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "un café").expect("Unable to ingest data");
+        assert_eq!(None, index.search_strict("café").unwrap());
+    }
+
+    #[test]
+    fn search_raw_and_stemmed_words() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_indexed_stems());
+        index.ingest("file1.txt".to_string(), "Apples").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "apple").expect("Unable to ingest data");
+        assert_eq!(vec!["file1.txt"], index.search("apples").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], index.search("apple").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_stemmed("apple").unwrap().unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_stemmed("apples").unwrap().unwrap());
+        assert_eq!(None, index.search("stem apple").unwrap());
+    }
+
+    #[test]
+    fn stemmed_search_requires_indexed_stems() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "apples").expect("Unable to ingest data");
+        assert_eq!(None, index.search_stemmed("apple").unwrap());
+    }
+
+    #[test]
+    fn restore_full_config() {
+        let config = IndexConfig::new(0.01).with_stop_words(vec!["the", "a"]).with_min_token_length(2).with_max_token_length(10).with_preserved_accents();
+        let mut index = Index::with_config(config.clone());
+        index.ingest("file1.txt".to_string(), "the word1 word2 a").expect("Unable to ingest data");
+        let dump = serde_json::to_string(&index).expect("Unable to serialize index");
+        let restored = Index::restore(&dump);
+        assert_eq!(&config, restored.config());
+        assert_eq!(index.bloom_filters, restored.bloom_filters);
+        assert_eq!(vec!["file1.txt"], restored.search("the word1 word2").unwrap().unwrap());
+        assert_eq!(None, restored.search("a").unwrap());
+    }
+
+    #[test]
+    fn restore_with_incompatible_config() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_locale(Locale::Turkish));
+        index.ingest("file1.txt".to_string(), "ISTANBUL").expect("Unable to ingest data");
+        let dump = serde_json::to_string(&index).expect("Unable to serialize index");
+        let restored = Index::restore_with_config(&dump, IndexConfig::new(0.001).with_locale(Locale::Turkish)).unwrap();
+        assert_eq!(0.001, restored.config().error_rate);
+        assert!(restored.tokenizer_fingerprint_matches());
+        assert_eq!(vec!["file1.txt"], restored.search("ıstanbul").unwrap().unwrap());
+        assert!(matches!(Index::restore_with_config(&dump, IndexConfig::new(0.01)), Err(Error::IncompatibleConfig)));
+        let restored = Index::restore_with_config_unchecked(&dump, IndexConfig::new(0.01)).unwrap();
+        assert_eq!(&IndexConfig::new(0.01), restored.config());
+        assert!(!restored.tokenizer_fingerprint_matches());
+        let dump = serde_json::to_string(&restored).expect("Unable to serialize index");
+        assert!(matches!(Index::restore_with_config(&dump, IndexConfig::new(0.01)), Err(Error::IncompatibleConfig)));
+        let legacy_dump = "{\"error_rate\":0.1,\"preserve_accents\":true,\"bloom_filters\":{}}";
+        assert!(matches!(Index::restore_with_config(legacy_dump, IndexConfig::new(0.1)), Err(Error::IncompatibleConfig)));
+        assert!(Index::restore_with_config(legacy_dump, IndexConfig::new(0.1).with_preserved_accents()).is_ok());
+        assert!(matches!(Index::restore_with_config("{", IndexConfig::new(0.1)), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn deterministic_dump() {
+        let mut index = Index::new(0.01);
+        for position in (0..20).rev() {
+            index.ingest(format!("file{:02}.txt", position), &format!("word{}", position)).expect("Unable to ingest data");
+            index.ingest_sections(format!("sections{:02}.txt", position), &[("title", "word1"), ("body", "word2"), ("author", "word3")]).expect("Unable to ingest data");
+        }
+        let dump = serde_json::to_string(&index).expect("Unable to serialize index");
+        for _ in 0..5 {
+            let restored = Index::restore(&dump);
+            assert_eq!(dump, serde_json::to_string(&restored).expect("Unable to serialize index"));
+        }
+        let positions: Vec<usize> = (0..20).map(|position| dump.find(&format!("\"file{:02}.txt\":{{", position)).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(dump.find("\"author\"").unwrap() < dump.find("\"body\"").unwrap());
+    }
+
+    #[test]
+    fn restore_truncated_dump() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        let dump = serde_json::to_string(&index).expect("Unable to serialize index");
+        assert_eq!(vec!["file1.txt"], Index::try_restore(&dump).unwrap().search("word1").unwrap().unwrap());
+        for length in &[0, 1, dump.len() / 2, dump.len() - 1] {
+            assert!(matches!(Index::try_restore(&dump[..*length]), Err(Error::TruncatedDump)), "{} bytes dump", length);
+        }
+        assert!(matches!(Index::try_restore("{\"error_rate\":\"high\"}"), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn dump_to_writer() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_stop_words(vec!["the"]));
+        index.ingest("file1.txt".to_string(), "the word1 word2").expect("Unable to ingest data");
+        index.ingest_sections("file2.txt".to_string(), &[("title", "word3")]).expect("Unable to ingest data");
+        let mut dump = Vec::new();
+        index.dump(&mut dump).expect("Unable to dump index");
+        let restored = Index::try_restore(str::from_utf8(&dump).unwrap()).unwrap();
+        assert_eq!(index.config(), restored.config());
+        assert_eq!(vec!["file1.txt"], restored.search("word1 word2").unwrap().unwrap());
+        assert_eq!(vec!["file2.txt"], restored.search_fields("title:word3").unwrap().unwrap());
+        let mut restored_dump = Vec::new();
+        restored.dump(&mut restored_dump).expect("Unable to dump index");
+        assert_eq!(dump, restored_dump);
+    }
+
+    #[test]
+    fn search_streaming_dump() {
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_stop_words(vec!["the"]));
+        index.ingest("file1.txt".to_string(), "the word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word2 word3").expect("Unable to ingest data");
+        index.ingest("file3.txt".to_string(), "word3 Café").expect("Unable to ingest data");
+        let mut dump = Vec::new();
+        index.dump_lines(&mut dump).expect("Unable to dump index");
+        assert_eq!(4, str::from_utf8(&dump).unwrap().lines().count());
+        for keywords in &["word1", "word2", "word3 cafe", "word1 word3", "the", "unknown"] {
+            let expected: Vec<String> = index.search(keywords).unwrap().unwrap_or_default().into_iter().cloned().collect();
+            assert_eq!(expected, Index::search_streaming_dump(dump.as_slice(), keywords).unwrap(), "searching {}", keywords);
+        }
+        assert!(matches!(Index::search_streaming_dump(&b""[..], "word1"), Err(Error::TruncatedDump)));
+        assert!(matches!(Index::search_streaming_dump(&dump[..dump.len() - 10], "word1"), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn dump_to_file_atomically() {
+        let path = env::temp_dir().join(format!("index_bloom_atomic_dump_{}.json", process::id()));
+        let temporary_path = temporary_dump_path(&path);
+        assert_eq!(Some("index_bloom_atomic_dump_".to_string() + &process::id().to_string() + ".json.tmp"), temporary_path.file_name().map(|name| name.to_string_lossy().into_owned()));
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "word1").expect("Unable to ingest data");
+        index.dump_to_file(&path).expect("Unable to dump index");
+        assert!(!temporary_path.exists());
+        let first_dump = fs::read_to_string(&path).expect("Unable to read dump file");
+        // A temporary file which cannot be written leaves the previous dump untouched
+        fs::create_dir(&temporary_path).expect("Unable to create directory");
+        index.ingest("file2.txt".to_string(), "word2").expect("Unable to ingest data");
+        assert!(matches!(index.dump_to_file(&path), Err(Error::Io(_))));
+        fs::remove_dir(&temporary_path).expect("Unable to remove directory");
+        assert_eq!(first_dump, fs::read_to_string(&path).expect("Unable to read dump file"));
+        index.dump_to_file(&path).expect("Unable to dump index");
+        let restored = Index::try_restore(&fs::read_to_string(&path).expect("Unable to read dump file")).unwrap();
+        fs::remove_file(&path).expect("Unable to remove dump file");
+        assert_eq!(vec!["file2.txt"], restored.search("word2").unwrap().unwrap());
+    }
+
+    #[test]
+    fn auto_dump_when_dropped() {
+        let path = env::temp_dir().join(format!("index_bloom_auto_dump_{}.json", process::id()));
+        let mut index = Index::with_config(IndexConfig::new(0.01).with_stop_words(vec!["the"]));
+        index.ingest("file1.txt".to_string(), "the word1 word2\nword3").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        index.auto_dump_on_drop(&path);
+        let expected_config = index.config().clone();
+        let expected_filters = serde_json::to_value(&index.bloom_filters).expect("Unable to serialize filters");
+        drop(index);
+        let dump = fs::read_to_string(&path).expect("Unable to read dump file");
+        fs::remove_file(&path).expect("Unable to remove dump file");
+        let restored = Index::restore(&dump);
+        assert_eq!(&expected_config, restored.config());
+        assert_eq!(expected_filters, serde_json::to_value(&restored.bloom_filters).expect("Unable to serialize filters"));
+        assert_eq!(vec!["file1.txt", "file2.txt"], restored.search("word1 word3").unwrap().unwrap());
+    }
+
+    #[test]
+    fn search_with_minimum_query_length() {
+        let mut index = Index::new(0.01);
+        index.ingest("file1.txt".to_string(), "a word1 word2").expect("Unable to ingest data");
+        index.ingest("file2.txt".to_string(), "word1 word3").expect("Unable to ingest data");
+        assert_eq!(None, index.search("word1 x").unwrap());
+        assert_eq!(vec!["file1.txt", "file2.txt"], index.search_with_min_query_len("word1 x", 2).unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_with_min_query_len("x word1 word2", 2).unwrap().unwrap());
+        assert_eq!(vec!["file1.txt"], index.search_with_min_query_len("a", 1).unwrap().unwrap());
+        assert_eq!(None, index.search_with_min_query_len("a", 2).unwrap());
+        assert_eq!(index.search("word1 word3").unwrap(), index.search_with_min_query_len("word1 word3", 0).unwrap());
+    }
+
+    #[test]
+    fn search_annotated_with_estimated_terms() {
+        let mut index = Index::new(0.01);
+        let long_content = (0..200).map(|word| format!("word{}", word)).collect::<Vec<String>>().join(" ");
+        index.ingest("long.txt".to_string(), &long_content).expect("Unable to ingest data");
+        index.ingest("short.txt".to_string(), "word1 word2 word3").expect("Unable to ingest data");
+        index.ingest("other.txt".to_string(), "other").expect("Unable to ingest data");
+        let hits = index.search_annotated("word1 word2").unwrap();
+        assert_eq!(vec![&"long.txt".to_string(), &"short.txt".to_string()], hits.iter().map(|(name, _)| *name).collect::<Vec<&String>>());
+        for (name, terms) in &hits {
+            assert_eq!(Some(*terms), index.estimated_terms(*name));
+        }
+        assert!((180..220).contains(&hits[0].1), "{} terms estimated", hits[0].1);
+        assert!(hits[1].1 <= 5, "{} terms estimated", hits[1].1);
+        assert_eq!(None, index.estimated_terms("unknown.txt"));
+        assert!(index.search_annotated("unknown").unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_with_minimum_confidence() {
+        let mut index = Index::new(0.0001);
+        index.ingest("saturated.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("sparse.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        for token in 0..4 {
+            Arc::make_mut(index.bloom_filters.get_mut("saturated.txt").unwrap()).insert(&format!("noise{}", token)).unwrap();
+        }
+        let saturated_confidence = 1.0 - index.bloom_filters["saturated.txt"].false_positive_rate();
+        let sparse_confidence = 1.0 - index.bloom_filters["sparse.txt"].false_positive_rate();
+        assert!(saturated_confidence > 0.5 && saturated_confidence < 0.995 && sparse_confidence > 0.999, "confidences {} {}", saturated_confidence, sparse_confidence);
+        assert_eq!(vec!["saturated.txt", "sparse.txt"], index.search_min_confidence("word1", 0.5).unwrap().unwrap());
+        assert_eq!(vec!["sparse.txt"], index.search_min_confidence("word1", 0.995).unwrap().unwrap());
+        assert_eq!(index.search("word1 word2").unwrap(), index.search_min_confidence("word1 word2", 0.0).unwrap());
+        assert_eq!(None, index.search_min_confidence("word1", 1.0).unwrap());
+    }
+
+    #[test]
+    fn scored_search_penalizes_saturation() {
+        let mut index = Index::new(0.01);
+        index.ingest("saturated.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        index.ingest("sparse.txt".to_string(), "word1 word2").expect("Unable to ingest data");
+        for token in 0..100 {
+            Arc::make_mut(index.bloom_filters.get_mut("saturated.txt").unwrap()).insert(&format!("noise{}", token)).unwrap();
+        }
+        let hits = index.search_scored("word1 word2").unwrap();
+        assert_eq!(vec![&"sparse.txt".to_string(), &"saturated.txt".to_string()], hits.iter().map(|(name, _)| *name).collect::<Vec<&String>>());
+        assert!(hits[0].1 > hits[1].1);
+        let fill_ratio = index.bloom_filters["sparse.txt"].fill_ratio();
+        assert_eq!(2.0 / (1.0 + fill_ratio), hits[0].1);
+        assert!(index.search_scored("word3").unwrap().iter().all(|(name, _)| *name == "saturated.txt"));
+    }
+
+    #[test]
+    #[allow(clippy::expect_fun_call)]
     fn restore_from_str() {
         let path = "./test/data/test_restore.json";
         let index_content = fs::read_to_string(path).expect(format!("Unable to read dump file {}", &path).as_str());
@@ -21,11 +21,26 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! # Optional features
+//!
+//! - `gzip`: [`Index::ingest_file`] decompresses files with a `.gz` extension.
+//! - `parallel`: [`Index::ingest_parallel`] tokenizes and hashes large documents on several threads.
+//! - `segmentation`: [`IndexConfig::with_unicode_word_boundaries`] splits words on Unicode word boundaries.
 
 mod index;
-pub use crate::index::Index;
+pub use crate::index::{diff_search, DocumentKey, DocumentStats, DocumentTrace, Index, IngestStats, Iter, RemovalStats, SearchStats, SearchTrace, SecondarySort};
+mod config;
+pub use crate::config::{EmptyWordPolicy, FieldCombiner, HashErrorPolicy, IndexConfig, Locale};
 mod errors;
-pub use crate::errors::Error;
-
+pub use crate::errors::{Error, Warning};
 mod bloom_filter;
+pub use crate::bloom_filter::{BitOrder, BloomFilter};
+mod counting_bloom_filter;
+pub use crate::counting_bloom_filter::CountingBloomFilter;
+
+pub mod analyzer;
+mod cardinality;
+mod query;
+mod random;
 mod tokens;
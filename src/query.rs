@@ -0,0 +1,221 @@
+use crate::errors::Error;
+
+/// A boolean expression of words, as parsed by [`Query::parse`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(String),
+    And(Vec<Query>),
+    Or(Vec<Query>)
+}
+
+/// The maximum nesting of parentheses in a query, so that parsing cannot overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lexeme<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    Word(&'a str)
+}
+
+impl Query {
+    /// Parses a query made of words, uppercase `AND` and `OR` operators and parentheses.
+    ///
+    /// Words without operator between them are joined with `AND`, which takes precedence over `OR`.
+    /// Parentheses cannot be nested more than 64 levels deep.
+    pub fn parse(query: &str) -> Result<Query, Error> {
+        let lexemes = lex(query);
+        let mut position = 0;
+        let expression = parse_or(&lexemes, &mut position, 0)?;
+        if position < lexemes.len() {
+            return Err(Error::InvalidQuery);
+        }
+        Ok(expression)
+    }
+
+    /// Replaces each word by the tokens returned by `tokenize`, joined with `AND`.
+    ///
+    /// Words without any token are dropped, as well as the operators left without operand. Returns `None` if nothing is left.
+    pub fn tokenize<F: Fn(&str) -> Vec<String>>(self, tokenize: &F) -> Option<Query> {
+        match self {
+            Query::Term(word) => {
+                let tokens: Vec<Query> = tokenize(&word).into_iter().map(Query::Term).collect();
+                if tokens.is_empty() {
+                    None
+                } else {
+                    Some(flatten(tokens, Query::And))
+                }
+            },
+            Query::And(operands) => Query::tokenize_operands(operands, tokenize, Query::And),
+            Query::Or(operands) => Query::tokenize_operands(operands, tokenize, Query::Or)
+        }
+    }
+
+    /// Returns every word of the query.
+    pub fn terms(&self) -> Vec<&String> {
+        match self {
+            Query::Term(word) => vec![word],
+            Query::And(operands) | Query::Or(operands) => operands.iter().flat_map(Query::terms).collect()
+        }
+    }
+
+    /// Evaluates the query, testing each word with `contains`, for instance against a filter, and stopping as soon as the result is known.
+    pub fn matches<F: Fn(&str) -> Result<bool, Error>>(&self, contains: &F) -> Result<bool, Error> {
+        match self {
+            Query::Term(word) => contains(word),
+            Query::And(operands) => {
+                for operand in operands {
+                    if !operand.matches(contains)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            Query::Or(operands) => {
+                for operand in operands {
+                    if operand.matches(contains)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn tokenize_operands<F: Fn(&str) -> Vec<String>>(operands: Vec<Query>, tokenize: &F, operator: fn(Vec<Query>) -> Query) -> Option<Query> {
+        let operands: Vec<Query> = operands.into_iter().filter_map(|operand| operand.tokenize(tokenize)).collect();
+        if operands.is_empty() {
+            None
+        } else {
+            Some(flatten(operands, operator))
+        }
+    }
+}
+
+/// Splits a query into words, operators and parentheses.
+fn lex(query: &str) -> Vec<Lexeme<'_>> {
+    let mut lexemes = Vec::new();
+    for mut word in query.split_whitespace() {
+        while let Some(rest) = word.strip_prefix('(') {
+            lexemes.push(Lexeme::Open);
+            word = rest;
+        }
+        let mut closing = 0;
+        while let Some(rest) = word.strip_suffix(')') {
+            closing += 1;
+            word = rest;
+        }
+        match word {
+            "" => (),
+            "AND" => lexemes.push(Lexeme::And),
+            "OR" => lexemes.push(Lexeme::Or),
+            _ => lexemes.push(Lexeme::Word(word))
+        }
+        lexemes.extend((0..closing).map(|_| Lexeme::Close));
+    }
+    lexemes
+}
+
+fn parse_or(lexemes: &[Lexeme], position: &mut usize, depth: usize) -> Result<Query, Error> {
+    let mut operands = vec![parse_and(lexemes, position, depth)?];
+    while lexemes.get(*position) == Some(&Lexeme::Or) {
+        *position += 1;
+        operands.push(parse_and(lexemes, position, depth)?);
+    }
+    Ok(flatten(operands, Query::Or))
+}
+
+fn parse_and(lexemes: &[Lexeme], position: &mut usize, depth: usize) -> Result<Query, Error> {
+    let mut operands = vec![parse_operand(lexemes, position, depth)?];
+    loop {
+        match lexemes.get(*position) {
+            Some(Lexeme::And) => {
+                *position += 1;
+                operands.push(parse_operand(lexemes, position, depth)?);
+            },
+            Some(Lexeme::Open) | Some(Lexeme::Word(_)) => operands.push(parse_operand(lexemes, position, depth)?),
+            _ => break
+        }
+    }
+    Ok(flatten(operands, Query::And))
+}
+
+fn parse_operand(lexemes: &[Lexeme], position: &mut usize, depth: usize) -> Result<Query, Error> {
+    match lexemes.get(*position) {
+        Some(Lexeme::Word(word)) => {
+            *position += 1;
+            Ok(Query::Term(word.to_string()))
+        },
+        Some(Lexeme::Open) if depth < MAX_DEPTH => {
+            *position += 1;
+            let expression = parse_or(lexemes, position, depth + 1)?;
+            if lexemes.get(*position) != Some(&Lexeme::Close) {
+                return Err(Error::InvalidQuery);
+            }
+            *position += 1;
+            Ok(expression)
+        },
+        _ => Err(Error::InvalidQuery)
+    }
+}
+
+fn flatten(mut operands: Vec<Query>, operator: fn(Vec<Query>) -> Query) -> Query {
+    if operands.len() == 1 {
+        operands.remove(0)
+    } else {
+        operator(operands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(word: &str) -> Query {
+        Query::Term(word.to_string())
+    }
+
+    #[test]
+    fn parse_operators() {
+        assert_eq!(term("a"), Query::parse("a").unwrap());
+        assert_eq!(Query::And(vec![term("a"), term("b")]), Query::parse("a AND b").unwrap());
+        assert_eq!(Query::And(vec![term("a"), term("b")]), Query::parse("a b").unwrap());
+        assert_eq!(Query::Or(vec![term("a"), term("b")]), Query::parse("a OR b").unwrap());
+        assert_eq!(Query::And(vec![term("a"), term("or"), term("b")]), Query::parse("a or b").unwrap());
+    }
+
+    #[test]
+    fn parse_precedence_and_groups() {
+        let expected = Query::Or(vec![term("a"), Query::And(vec![term("b"), term("c")])]);
+        assert_eq!(expected, Query::parse("a OR b AND c").unwrap());
+        let expected = Query::And(vec![Query::Or(vec![term("a"), term("b")]), term("c")]);
+        assert_eq!(expected, Query::parse("(a OR b) AND c").unwrap());
+        assert_eq!(expected, Query::parse("( a OR b ) c").unwrap());
+        assert_eq!(term("a"), Query::parse("((a))").unwrap());
+    }
+
+    #[test]
+    fn reject_invalid_queries() {
+        for query in &["", "AND", "a AND", "OR b", "a AND OR b", "(a OR b", "a OR b)", "()"] {
+            assert!(matches!(Query::parse(query), Err(Error::InvalidQuery)), "{} should be invalid", query);
+        }
+    }
+
+    #[test]
+    fn reject_deeply_nested_queries() {
+        let nested = |depth: usize| format!("{}word{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(term("word"), Query::parse(&nested(MAX_DEPTH)).unwrap());
+        assert!(matches!(Query::parse(&nested(MAX_DEPTH + 1)), Err(Error::InvalidQuery)));
+        assert!(matches!(Query::parse(&nested(100_000)), Err(Error::InvalidQuery)));
+    }
+
+    #[test]
+    fn tokenize_terms() {
+        let query = Query::parse("(The OR a) AND Word").unwrap();
+        let tokenize = |word: &str| if word == "The" { Vec::new() } else { vec![word.to_lowercase()] };
+        assert_eq!(Some(Query::And(vec![term("a"), term("word")])), query.tokenize(&tokenize));
+        assert_eq!(None, Query::parse("The").unwrap().tokenize(&tokenize));
+    }
+}
@@ -0,0 +1,43 @@
+/// A SplitMix64 pseudo-random generator, giving reproducible sequences from a seed.
+///
+/// It is fast and good enough to sample documents, but must not be used where unpredictability matters.
+pub(crate) struct SplitMix64 {
+    state: u64
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64 {
+            state: seed
+        }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    /// Returns a number uniformly distributed between 0 included and `bound` excluded.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        // The high bits of the product are nearly uniform, without the bias of a modulo
+        ((u128::from(self.next_u64()) * bound as u128) >> 64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_reproducible_numbers() {
+        let mut generator = SplitMix64::new(42);
+        let numbers: Vec<u64> = (0..5).map(|_| generator.next_u64()).collect();
+        let mut other_generator = SplitMix64::new(42);
+        assert_eq!(numbers, (0..5).map(|_| other_generator.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(numbers[0], SplitMix64::new(43).next_u64());
+        assert!((0..1000).all(|_| generator.below(7) < 7));
+    }
+}
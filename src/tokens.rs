@@ -1,58 +1,183 @@
+use std::borrow::Cow;
 use std::str::SplitWhitespace;
 use unidecode::unidecode;
+#[cfg(feature = "segmentation")]
+use unicode_segmentation::{UnicodeSegmentation, UnicodeWords};
+
+use crate::config::{EmptyWordPolicy, Locale};
+
+/// The token of words left empty by normalization, with [`EmptyWordPolicy::Placeholder`].
+pub const EMPTY_WORD_PLACEHOLDER: &str = "\u{FFFD}";
+
+/// The words of a text, split on whitespace or on Unicode word boundaries.
+enum Words<'a> {
+    Whitespace(SplitWhitespace<'a>),
+    #[cfg(feature = "segmentation")]
+    Unicode(UnicodeWords<'a>)
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Words::Whitespace(words) => words.next(),
+            #[cfg(feature = "segmentation")]
+            Words::Unicode(words) => words.next()
+        }
+    }
+}
 
 pub struct Tokens<'a> {
-    words: SplitWhitespace<'a>
+    #[cfg_attr(not(feature = "segmentation"), allow(dead_code))]
+    text: &'a str,
+    words: Words<'a>,
+    fold_accents: bool,
+    locale: Locale,
+    empty_word_policy: EmptyWordPolicy,
+    empty_words: usize
 }
 
 impl<'a> Tokens<'a> {
     pub fn new(words: &'a str) -> Self {
         Tokens {
-            words: words.split_whitespace()
+            text: words,
+            words: Words::Whitespace(words.split_whitespace()),
+            fold_accents: true,
+            locale: Locale::Default,
+            empty_word_policy: EmptyWordPolicy::Skip,
+            empty_words: 0
+        }
+    }
+
+    pub fn accented(words: &'a str) -> Self {
+        Tokens {
+            text: words,
+            words: Words::Whitespace(words.split_whitespace()),
+            fold_accents: false,
+            locale: Locale::Default,
+            empty_word_policy: EmptyWordPolicy::Skip,
+            empty_words: 0
         }
     }
 
-    fn clean_word(&self, word: &str) -> String {
-        word.replace(".", "")
-            .replace("!", "")
-            .replace("?", "")
-            .replace(",", "")
-            .replace(";", "")
-            .replace(":", "")
-            .replace("/", "")
-            .replace("&", "")
-            .replace("#", "")
-            .replace("*", "")
-            .replace("_", "")
-            .replace("(", "")
-            .replace(")", "")
-            .replace("[", "")
-            .replace("]", "")
-            .replace("{", "")
-            .replace("}", "")
-            .replace("<", "")
-            .replace(">", "")
-            .replace("'", "")
-            .replace("`", "")
-            .replace("\"", "")
+    /// Lowercases words with the casing rules of `locale`.
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Splits words on the Unicode word boundaries of UAX #29 rather than on whitespace.
+    ///
+    /// Punctuation is not part of the words, so it is not stripped from them: `"don't"` is kept as is.
+    #[cfg(feature = "segmentation")]
+    pub fn with_word_boundaries(mut self) -> Self {
+        self.words = Words::Unicode(self.text.unicode_words());
+        self
+    }
+
+    /// Applies `policy` to the words left empty by normalization.
+    pub fn with_empty_word_policy(mut self, policy: EmptyWordPolicy) -> Self {
+        self.empty_word_policy = policy;
+        self
+    }
+
+    /// Returns the number of words left empty by normalization so far, whether they were skipped or replaced.
+    pub fn empty_words(&self) -> usize {
+        self.empty_words
     }
 }
 
+/// Lowercases the letters of `word` whose case differs in Turkish, `I` and `İ`, leaving the others unchanged.
+fn turkish_lowercase(word: &str) -> String {
+    word.chars()
+        .map(|character| match character {
+            'I' => 'ı',
+            'İ' => 'i',
+            _ => character
+        })
+        .collect()
+}
+
+/// Removes punctuation characters from `word`.
+pub fn clean_word(word: &str) -> String {
+    word.replace(".", "")
+        .replace("!", "")
+        .replace("?", "")
+        .replace(",", "")
+        .replace(";", "")
+        .replace(":", "")
+        .replace("/", "")
+        .replace("&", "")
+        .replace("#", "")
+        .replace("*", "")
+        .replace("_", "")
+        .replace("(", "")
+        .replace(")", "")
+        .replace("[", "")
+        .replace("]", "")
+        .replace("{", "")
+        .replace("}", "")
+        .replace("<", "")
+        .replace(">", "")
+        .replace("'", "")
+        .replace("`", "")
+        .replace("\"", "")
+}
+
 impl<'a> Iterator for Tokens<'a> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(word) = self.words.next() {
-            let ascii_word = unidecode(word);
-            let token = self.clean_word(&ascii_word).to_lowercase();
+        let whitespace_words = matches!(self.words, Words::Whitespace(_));
+        for word in self.words.by_ref() {
+            let word = match self.locale {
+                Locale::Default => Cow::Borrowed(word),
+                Locale::Turkish => Cow::Owned(turkish_lowercase(word))
+            };
+            let word = if self.fold_accents {
+                Cow::Owned(unidecode(&word))
+            } else {
+                word
+            };
+            let token = if whitespace_words {
+                clean_word(&word).to_lowercase()
+            } else {
+                // Transliterated ideographs end with a space
+                word.trim().to_lowercase()
+            };
             if !token.is_empty() {
                 return Some(token)
             }
+            self.empty_words += 1;
+            if self.empty_word_policy == EmptyWordPolicy::Placeholder {
+                return Some(EMPTY_WORD_PLACEHOLDER.to_string());
+            }
         }
         None
     }
 }
 
+/// Removes the invisible characters of `text` which would split or alter words.
+///
+/// Zero-width spaces become regular spaces, other zero-width characters and combining marks are removed.
+pub fn strip_invisible(text: &str) -> String {
+    text.chars()
+        .filter_map(|character| match character {
+            '\u{200B}' => Some(' '),
+            '\u{00AD}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => None,
+            '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}' => None,
+            _ => Some(character)
+        })
+        .collect()
+}
+
+/// Prefixes `token` with a `namespace`.
+///
+/// Tokens never contain whitespace, so a namespaced token cannot collide with a plain one.
+pub fn namespaced(namespace: &str, token: &str) -> String {
+    format!("{} {}", namespace, token)
+}
 
 #[cfg(test)]
 mod tests {
@@ -124,6 +249,75 @@ mod tests {
     }
 
     #[test]
+    fn keep_accents() {
+        let mut tokens = Tokens::accented("Café, CAFE éèêàïùç");
+        assert_eq!(tokens.next().unwrap(), "café");
+        assert_eq!(tokens.next().unwrap(), "cafe");
+        assert_eq!(tokens.next().unwrap(), "éèêàïùç");
+        assert_eq!(tokens.next(), None);
+    }
+
+    #[test]
+    fn lowercase_with_locale() {
+        let tokens: Vec<String> = Tokens::accented("İstanbul ILIK").collect();
+        assert_eq!(vec!["i\u{307}stanbul", "ilik"], tokens);
+        let tokens: Vec<String> = Tokens::accented("İstanbul ILIK").with_locale(Locale::Turkish).collect();
+        assert_eq!(vec!["istanbul", "ılık"], tokens);
+        let tokens: Vec<String> = Tokens::new("İstanbul ILIK").with_locale(Locale::Turkish).collect();
+        assert_eq!(vec!["istanbul", "ilik"], tokens);
+    }
+
+    #[cfg(feature = "segmentation")]
+    #[test]
+    fn split_on_word_boundaries() {
+        let text = "Hello,world! (foo)bar e-mail don't 3.14";
+        let tokens: Vec<String> = Tokens::new(text).collect();
+        assert_eq!(vec!["helloworld", "foobar", "e-mail", "dont", "314"], tokens);
+        let tokens: Vec<String> = Tokens::new(text).with_word_boundaries().collect();
+        assert_eq!(vec!["hello", "world", "foo", "bar", "e", "mail", "don't", "3.14"], tokens);
+        let text = "東京タワー Ελληνικά,λέξεις";
+        let tokens: Vec<String> = Tokens::accented(text).collect();
+        assert_eq!(vec!["東京タワー", "ελληνικάλέξεις"], tokens);
+        let tokens: Vec<String> = Tokens::accented(text).with_word_boundaries().collect();
+        assert_eq!(vec!["東", "京", "タワー", "ελληνικά", "λέξεις"], tokens);
+        let tokens: Vec<String> = Tokens::new(text).with_word_boundaries().collect();
+        assert_eq!(vec!["dong", "jing", "tawa", "ellenika", "lexeis"], tokens);
+    }
+
+    #[test]
+    fn count_empty_words() {
+        let mut tokens = Tokens::new("word1 ☃ !!! word2");
+        assert_eq!(vec!["word1", "word2"], tokens.by_ref().collect::<Vec<String>>());
+        assert_eq!(2, tokens.empty_words());
+        let mut tokens = Tokens::new("word1 ☃ !!! word2").with_empty_word_policy(EmptyWordPolicy::Placeholder);
+        assert_eq!(vec!["word1", EMPTY_WORD_PLACEHOLDER, EMPTY_WORD_PLACEHOLDER, "word2"], tokens.by_ref().collect::<Vec<String>>());
+        assert_eq!(2, tokens.empty_words());
+        assert_eq!(vec![EMPTY_WORD_PLACEHOLDER], Tokens::new(EMPTY_WORD_PLACEHOLDER).with_empty_word_policy(EmptyWordPolicy::Placeholder).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn strip_invisible_characters() {
+        let text = strip_invisible("word1\u{200B}word2 wo\u{200D}rd3\u{FEFF} cafe\u{0301} na\u{0308}ive");
+        assert_eq!("word1 word2 word3 cafe naive", text);
+        let mut tokens = Tokens::accented(&text);
+        assert_eq!(tokens.next().unwrap(), "word1");
+        assert_eq!(tokens.next().unwrap(), "word2");
+        assert_eq!(tokens.next().unwrap(), "word3");
+        assert_eq!(tokens.next().unwrap(), "cafe");
+        assert_eq!(tokens.next().unwrap(), "naive");
+        assert_eq!(tokens.next(), None);
+        assert_eq!("café", strip_invisible("café"));
+    }
+
+    #[test]
+    fn namespaced_tokens_never_collide() {
+        let token = namespaced("accent", "café");
+        assert_eq!("accent café", token);
+        assert_eq!(2, Tokens::new(&token).count());
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn empty_tokens() {
         let mut tokens = Tokens::new("");
         assert_eq!(tokens.next(), None);
@@ -0,0 +1,43 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use index_bloom::BloomFilter;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+// Declared in its own test binary, so that the other tests keep the system allocator
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made by the current thread while running `f`.
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|allocations| allocations.get());
+    f();
+    ALLOCATIONS.with(|allocations| allocations.get()) - before
+}
+
+#[test]
+fn contains_without_allocation() {
+    let mut filter = BloomFilter::new(100, 0.0001);
+    for i in 0..100 {
+        filter.insert(&format!("word{}", i)).expect("Unable to insert word");
+    }
+    assert!(count_allocations(|| drop(BloomFilter::new(100, 0.0001))) > 0);
+    assert_eq!(0, count_allocations(|| assert!(filter.contains("word1").unwrap())));
+    assert_eq!(0, count_allocations(|| assert!(!filter.contains("absent").unwrap())));
+}
@@ -0,0 +1,77 @@
+use std::process::{Command, Output};
+use std::{env, fs, process};
+
+fn web_bloom(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_web-bloom"))
+        .args(args)
+        .output()
+        .expect("Unable to run web-bloom")
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+    String::from_utf8(output.stdout.clone()).expect("Invalid UTF-8 output").lines().map(String::from).collect()
+}
+
+#[test]
+fn search_restored_index() {
+    let output = web_bloom(&["--restore", "./test/data/test_restore.json", "--search", "word1 word2"]);
+    assert!(output.status.success());
+    assert_eq!(vec!["file1.txt"], stdout_lines(&output));
+}
+
+#[test]
+fn search_without_match_fails() {
+    let output = web_bloom(&["--restore", "./test/data/test_restore.json", "--search", "unknown"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(stdout_lines(&output).is_empty());
+}
+
+#[test]
+fn search_with_json_output() {
+    let output = web_bloom(&["--restore", "./test/data/test_restore.json", "--search", "word1", "--format", "json"]);
+    assert!(output.status.success());
+    let hits: serde_json::Value = serde_json::from_slice(&output.stdout).expect("Invalid JSON output");
+    let hits = hits.as_array().expect("Output is not a JSON array");
+    assert_eq!(1, hits.len());
+    assert_eq!(Some("file1.txt"), hits[0]["name"].as_str());
+    assert!(hits[0]["false_positive_rate"].is_f64());
+    let output = web_bloom(&["--restore", "./test/data/test_restore.json", "--search", "unknown", "--format", "json"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("[]", String::from_utf8(output.stdout).unwrap().trim());
+}
+
+#[test]
+fn index_dump_and_search() {
+    let document = env::temp_dir().join(format!("index_bloom_cli_document_{}.txt", process::id()));
+    let dump = env::temp_dir().join(format!("index_bloom_cli_dump_{}.json", process::id()));
+    fs::write(&document, "A very very long content...").expect("Unable to write document");
+    let document = document.to_str().unwrap();
+    let output = web_bloom(&["--index", document, "--dump", dump.to_str().unwrap()]);
+    assert!(output.status.success());
+    let output = web_bloom(&["--restore", dump.to_str().unwrap(), "--search", "long content"]);
+    fs::remove_file(document).expect("Unable to remove document");
+    fs::remove_file(&dump).expect("Unable to remove dump");
+    assert!(output.status.success());
+    assert_eq!(vec![document.to_string()], stdout_lines(&output));
+}
+
+#[test]
+fn reject_invalid_arguments() {
+    let output = web_bloom(&["--search"]);
+    assert_eq!(Some(2), output.status.code());
+    let output = web_bloom(&["--unknown"]);
+    assert_eq!(Some(2), output.status.code());
+    let output = web_bloom(&["--search", "word1", "--format", "xml"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn restore_truncated_dump_fails() {
+    let dump = env::temp_dir().join(format!("index_bloom_cli_truncated_{}.json", process::id()));
+    let content = fs::read_to_string("./test/data/test_restore.json").expect("Unable to read dump");
+    fs::write(&dump, &content[..content.len() / 2]).expect("Unable to write dump");
+    let output = web_bloom(&["--restore", dump.to_str().unwrap(), "--search", "word1"]);
+    fs::remove_file(&dump).expect("Unable to remove dump");
+    assert_eq!(Some(2), output.status.code());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("truncated"));
+}